@@ -0,0 +1,180 @@
+//! `#[command]` and `#[derive(Dispatch)]` for the hanab-live crate.
+
+#![warn(clippy::pedantic, clippy::nursery)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, DeriveInput, Fields, ItemStruct, LitStr,
+};
+
+/// Makes a struct a websocket command with the given name: implements
+/// `Command`, and gives the fields hanab.live's camelCase names. `None`
+/// fields are left out when serializing. Goes above the `derive`:
+///
+/// ```ignore
+/// #[command(name = "tableJoin")]
+/// #[derive(Debug, Serialize)]
+/// pub struct TableJoin { /* ... */ }
+/// ```
+///
+/// This is an attribute rather than a derive because a derive can't add
+/// attributes to the struct it's on, and the serde ones are most of the
+/// boilerplate it replaces.
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown command attribute"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let mut item = parse_macro_input!(item as ItemStruct);
+    let Some(name) = name else {
+        return syn::Error::new_spanned(
+            &item.ident,
+            "missing #[command(name = \"...\")]",
+        )
+        .into_compile_error()
+        .into();
+    };
+    // Before the derives, which need to see what it adds
+    item.attrs.insert(
+        0,
+        parse_quote! {
+            #[::hanab_live::__private::serde_with::skip_serializing_none]
+        },
+    );
+    // Helper attributes go after the derives that introduce them
+    item.attrs
+        .push(parse_quote!(#[serde(rename_all = "camelCase")]));
+    let ident = &item.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        item.generics.split_for_impl();
+    quote! {
+        #item
+
+        impl #impl_generics ::hanab_live::command::Command
+            for #ident #ty_generics #where_clause
+        {
+            const NAME: &'static str = #name;
+        }
+    }
+    .into()
+}
+
+/// On an enum whose variants each wrap one command, implements `Dispatch`,
+/// deserializing a message into the variant registered for its name.
+///
+/// Two variants with the same name are a compile error. Variants marked
+/// `#[dispatch(skip)]` aren't registered:
+///
+/// ```ignore
+/// #[derive(Debug, Dispatch)]
+/// pub enum ServerMessage {
+///     TableGone(TableGone),
+///     /* ... */
+///     #[dispatch(skip)]
+///     Unknown { name: String },
+/// }
+/// ```
+#[proc_macro_derive(Dispatch, attributes(dispatch))]
+pub fn derive_dispatch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let result = match &input.data {
+        syn::Data::Enum(data) => derive_enum(&input, data),
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            "Dispatch can only be derived for enums",
+        )),
+    };
+    result.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn derive_enum(
+    input: &DeriveInput,
+    data: &syn::DataEnum,
+) -> syn::Result<TokenStream2> {
+    let mut variants = Vec::new();
+    let mut types = Vec::new();
+    for variant in &data.variants {
+        if is_skipped(variant)? {
+            continue;
+        }
+        match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                variants.push(&variant.ident);
+                types.push(&fields.unnamed[0].ty);
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "each variant must wrap exactly one command",
+                ))
+            }
+        }
+    }
+    // The names are only known once the Command impls are, so they're
+    // compared by the compiler
+    let mut distinct = Vec::new();
+    for (i, (a, a_ty)) in variants.iter().zip(&types).enumerate() {
+        for (b, b_ty) in variants.iter().zip(&types).skip(i + 1) {
+            let message = format!("{a} and {b} have the same command name");
+            distinct.push(quote! {
+                assert!(
+                    !::hanab_live::__private::str_eq(
+                        <#a_ty as ::hanab_live::command::Command>::NAME,
+                        <#b_ty as ::hanab_live::command::Command>::NAME,
+                    ),
+                    #message,
+                );
+            });
+        }
+    }
+    let ident = &input.ident;
+    Ok(quote! {
+        const _: () = {
+            #(#distinct)*
+        };
+
+        impl ::hanab_live::command::Dispatch for #ident {
+            fn dispatch(
+                name: &str,
+                data: &str,
+            ) -> ::std::option::Option<::hanab_live::__private::serde_json::Result<Self>> {
+                #(
+                    if name == <#types as ::hanab_live::command::Command>::NAME {
+                        return ::std::option::Option::Some(
+                            ::hanab_live::__private::serde_json::from_str::<#types>(data)
+                                .map(Self::#variants),
+                        );
+                    }
+                )*
+                ::std::option::Option::None
+            }
+        }
+    })
+}
+
+fn is_skipped(variant: &syn::Variant) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in &variant.attrs {
+        if !attr.path().is_ident("dispatch") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown dispatch attribute"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use eyre::{eyre, WrapErr};
+use reqwest::cookie::CookieStore;
+use serde_json::json;
+use url::Url;
+
+/// Log in to the hanab.live server at `server` and return the session
+/// cookie, to be sent as the `Cookie` header when opening the websocket.
+/// hanab.live creates the account if it doesn't exist yet.
+///
+/// # Errors
+///
+/// If the request fails, the server rejects the credentials, or no cookie
+/// is returned.
+pub async fn authenticate_and_get_cookie(
+    server: &Url,
+    username: &str,
+    password: &str,
+) -> eyre::Result<http::HeaderValue> {
+    let url = crate::login_url(server)?;
+
+    // Temporary client
+    let jar = Arc::new(reqwest::cookie::Jar::default());
+    let client = reqwest::ClientBuilder::new()
+        .cookie_provider(jar.clone())
+        .build()?;
+    let response = client
+        .post(url.clone())
+        .form(&json!({
+            "username": username,
+            "password": password,
+            "version": "bot",
+        }))
+        .send()
+        .await?;
+    response.error_for_status_ref().wrap_err_with(|| {
+        format!("Authentication failed. Server response: {response:#?}")
+    })?;
+    let cookie = jar.cookies(&url).ok_or_else(|| {
+        eyre!("No cookie was received from hanab.live server")
+    })?;
+    Ok(cookie)
+}
@@ -0,0 +1,206 @@
+use serde::{Serialize, Serializer};
+
+use super::{command, TableID};
+
+// --- Lobby
+
+// Current Table
+
+#[command(name = "tableCreate")]
+#[derive(Debug, Serialize)]
+pub struct TableCreate {
+    pub name: Option<String>,
+    pub max_players: u8,
+    pub password: Option<String>,
+}
+impl Default for TableCreate {
+    fn default() -> Self {
+        Self {
+            name: None,
+            max_players: 6,
+            password: None,
+        }
+    }
+}
+
+#[command(name = "tableJoin")]
+#[derive(Debug, Serialize)]
+pub struct TableJoin {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    pub password: Option<String>,
+}
+
+#[command(name = "tableLeave")]
+#[derive(Debug, Serialize)]
+pub struct TableLeave {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "tableUnattend")]
+#[derive(Debug, Serialize)]
+pub struct TableUnattend {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "tableReattend")]
+#[derive(Debug, Serialize)]
+pub struct TableReattend {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "tableSetVariant")]
+#[derive(Debug, Serialize)]
+pub struct TableSetVariant {}
+
+#[command(name = "tableSetLeader")]
+#[derive(Debug, Serialize)]
+pub struct TableSetLeader {}
+
+#[command(name = "tableStart")]
+#[derive(Debug, Serialize)]
+pub struct TableStart {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    // TODO necessary?
+    // replay: bool,
+}
+
+#[command(name = "tableVoteForTermination")]
+#[derive(Debug, Serialize)]
+pub struct TableVoteForTermination {}
+
+#[command(name = "tableTerminate")]
+#[derive(Debug, Serialize)]
+pub struct TableTerminate {}
+
+#[command(name = "tableRestart")]
+#[derive(Debug, Serialize)]
+pub struct TableRestart {}
+
+#[command(name = "tableUpdate")]
+#[derive(Debug, Serialize)]
+pub struct TableUpdate {}
+
+#[command(name = "tableSuggest")]
+#[derive(Debug, Serialize)]
+pub struct TableSuggest {}
+
+// Chat
+
+#[command(name = "chat")]
+#[derive(Debug, Serialize)]
+pub struct Chat {
+    pub msg: String,
+    // e.g. "lobby" or "table123"
+    pub room: String,
+}
+
+#[command(name = "chatPM")]
+#[derive(Debug, Serialize)]
+pub struct ChatPM {
+    pub msg: String,
+    pub recipient: String,
+    pub room: String,
+}
+
+#[command(name = "chatRead")]
+#[derive(Debug, Serialize)]
+pub struct ChatRead {}
+
+// Other commands
+
+#[command(name = "getName")]
+#[derive(Debug, Serialize)]
+pub struct GetName;
+
+// --- In game
+
+#[command(name = "getGameInfo1")]
+#[derive(Debug, Serialize)]
+pub struct GetGameInfo1 {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "getGameInfo2")]
+#[derive(Debug, Serialize)]
+pub struct GetGameInfo2 {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "loaded")]
+#[derive(Debug, Serialize)]
+pub struct Loaded {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "action")]
+#[derive(Debug, Serialize)]
+pub struct Action {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    #[serde(rename = "type")]
+    pub kind: ActionType,
+    // Card order for plays and discards, player index for clues
+    pub target: usize,
+    // Clue value
+    pub value: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ActionType {
+    Play = 0,
+    Discard = 1,
+    ColorClue = 2,
+    RankClue = 3,
+}
+impl Serialize for ActionType {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+#[command(name = "note")]
+#[derive(Debug, Serialize)]
+pub struct Note {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    pub order: usize,
+    pub note: String,
+}
+
+#[command(name = "pause")]
+#[derive(Debug, Serialize)]
+pub struct Pause {}
+
+#[cfg(test)]
+mod tests {
+    use super::{TableCreate, TableJoin};
+    use crate::command::Command;
+
+    #[test]
+    fn serializes_commands() {
+        let create = TableCreate {
+            name: Some("fun".to_owned()),
+            ..TableCreate::default()
+        };
+        assert_eq!(
+            create.serialize_command(),
+            r#"tableCreate {"name":"fun","maxPlayers":6}"#
+        );
+        let join = TableJoin {
+            table_id: serde_json::from_str("5").unwrap(),
+            password: None,
+        };
+        assert_eq!(join.serialize_command(), r#"tableJoin {"tableID":5}"#);
+    }
+}
@@ -1,11 +1,15 @@
 use std::num::NonZeroU64;
 
-use color_eyre::eyre::{self, eyre};
+use eyre::eyre;
 use serde::{Deserialize, Deserializer, Serialize};
 
 pub mod client;
 pub mod server;
 
+pub use hanab_live_derive::{command, Dispatch};
+
+/// A websocket message. On the wire it is the name, a space, then the JSON
+/// payload.
 pub trait Command {
     const NAME: &'static str;
 
@@ -20,18 +24,41 @@ pub trait Command {
     }
 }
 
+/// A set of commands that a message can be deserialized into by name.
+/// Derived with `#[derive(Dispatch)]` on an enum with one variant per
+/// command.
+pub trait Dispatch: Sized {
+    /// None if `name` isn't one of the commands.
+    ///
+    /// # Errors
+    ///
+    /// If the payload doesn't deserialize into the command named.
+    fn dispatch(name: &str, data: &str) -> Option<serde_json::Result<Self>>;
+}
+
+/// Dispatches a server message to the first matching handler:
+///
+/// ```ignore
+/// Parse::from_str(&text)
+///     .handle_command(|chat: server::Chat| { /* ... */ })
+///     .handle_command(|user: server::User| { /* ... */ })
+///     .unhandled(|name, data| Ok(()))
+/// ```
 pub enum Parse<'a, O> {
     Break(eyre::Result<O>),
     Continue(&'a str, &'a str),
 }
 
 impl<'a, O> Parse<'a, O> {
+    #[allow(clippy::should_implement_trait)]
+    #[must_use]
     pub fn from_str(s: &'a str) -> Self {
         match s.split_once(' ') {
             Some((name, data)) => Self::Continue(name, data),
             None => Self::Break(Err(eyre!("error parsing command: no space"))),
         }
     }
+    #[must_use]
     pub fn handle_command<T, F>(self, f: F) -> Self
     where
         T: Command + Deserialize<'a>,
@@ -39,6 +66,7 @@ impl<'a, O> Parse<'a, O> {
     {
         self.handle_command_result(|x| Ok(f(x)))
     }
+    #[must_use]
     pub fn handle_command_result<T, F>(self, f: F) -> Self
     where
         T: Command + Deserialize<'a>,
@@ -55,6 +83,13 @@ impl<'a, O> Parse<'a, O> {
             _ => self,
         }
     }
+    /// Finish dispatching, calling `f` with the name and payload if no
+    /// handler matched.
+    ///
+    /// # Errors
+    ///
+    /// If the message was malformed, its payload failed to deserialize, or
+    /// the handler that ran returned an error.
     pub fn unhandled<F>(self, f: F) -> eyre::Result<O>
     where
         F: FnOnce(&'a str, &'a str) -> eyre::Result<O>,
@@ -70,10 +105,31 @@ impl<'a, O> Parse<'a, O> {
 #[serde(transparent)]
 pub struct UserID(i32);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    derive_more::Display,
+)]
 #[serde(transparent)]
 pub struct TableID(NonZeroU64);
 
+impl From<TableID> for u64 {
+    fn from(table_id: TableID) -> Self {
+        table_id.0.get()
+    }
+}
+
+/// For fields where hanab.live uses 0 to mean no table.
+///
+/// # Errors
+///
+/// If the value is not an unsigned integer.
 pub fn deserialize_option_table_id<'de, D>(
     deserializer: D,
 ) -> Result<Option<TableID>, D::Error>
@@ -0,0 +1,402 @@
+use eyre::{eyre, WrapErr};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
+
+use super::{command, deserialize_option_table_id, Dispatch, TableID, UserID};
+
+// Server messages
+
+/// Every server message, parsed in one step with `ServerMessage::parse`.
+/// Adding a variant registers the command with `Dispatch`.
+#[derive(Debug, Dispatch)]
+pub enum ServerMessage {
+    Warning(Warning),
+    Error(Error),
+    Welcome(Welcome),
+    Name(Name),
+    Table(Table),
+    TableList(TableList),
+    TableStart(TableStart),
+    TableProgress(TableProgress),
+    TableGone(TableGone),
+    User(User),
+    UserList(UserList),
+    UserLeft(UserLeft),
+    GameHistory(GameHistory),
+    Chat(Chat),
+    ChatList(ChatList),
+    ChatTyping(ChatTyping),
+    Joined(Joined),
+    Left(Left),
+    Init(Init),
+    GameAction(GameAction),
+    GameActionList(GameActionList),
+    DatabaseID(DatabaseID),
+    Connected(Connected),
+    Clock(Clock),
+    NoteListPlayer(NoteListPlayer),
+    /// A command this crate has no type for yet, kept so it can be
+    /// inspected or recorded. `raw` is a JSON string if the payload wasn't
+    /// JSON.
+    #[dispatch(skip)]
+    Unknown {
+        name: String,
+        raw: serde_json::Value,
+    },
+}
+
+impl ServerMessage {
+    /// Parses a websocket message: the command name, a space, then the JSON
+    /// payload.
+    ///
+    /// # Errors
+    ///
+    /// If there's no space, or a known command's payload doesn't match its
+    /// type.
+    pub fn parse(text: &str) -> eyre::Result<Self> {
+        let (name, data) = text
+            .split_once(' ')
+            .ok_or_else(|| eyre!("error parsing command: no space"))?;
+        Self::dispatch(name, data).map_or_else(
+            || {
+                Ok(Self::Unknown {
+                    name: name.to_owned(),
+                    raw: serde_json::from_str(data).unwrap_or_else(|_| {
+                        serde_json::Value::String(data.to_owned())
+                    }),
+                })
+            },
+            |result| result.wrap_err_with(|| format!("error parsing {name:?}")),
+        )
+    }
+}
+
+#[command(name = "warning")]
+#[derive(Debug, Deserialize)]
+pub struct Warning {
+    pub warning: String,
+}
+
+#[command(name = "error")]
+#[derive(Debug, Deserialize)]
+pub struct Error {
+    pub error: String,
+}
+
+// --- Lobby
+
+// General information
+
+#[command(name = "welcome")]
+#[derive(Debug, Deserialize)]
+pub struct Welcome {
+    #[serde(rename = "userID")]
+    pub user_id: UserID,
+    // pub random_table_name: String,
+}
+
+#[command(name = "name")]
+#[derive(Debug, Deserialize)]
+pub struct Name {
+    pub name: String,
+}
+
+// Tables
+
+#[command(name = "table")]
+#[derive(Debug, Deserialize)]
+pub struct Table {
+    pub id: TableID,
+    pub name: String,
+}
+
+#[command(name = "tableList")]
+#[derive(Debug, Deserialize)]
+pub struct TableList(pub Vec<Table>);
+
+#[command(name = "tableStart")]
+#[derive(Debug, Deserialize)]
+pub struct TableStart {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "tableProgress")]
+#[derive(Debug, Deserialize)]
+pub struct TableProgress {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "tableGone")]
+#[derive(Debug, Deserialize)]
+pub struct TableGone {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+// Users
+
+#[command(name = "user")]
+#[derive(Debug, Deserialize)]
+pub struct User {
+    #[serde(rename = "userID")]
+    pub user_id: UserID,
+    pub name: String,
+    pub status: UserStatus,
+    // 0 is used as sentinel for no TableID
+    #[serde(
+        rename = "tableID",
+        deserialize_with = "deserialize_option_table_id"
+    )]
+    pub table_id: Option<TableID>,
+}
+
+/// Where a user is, as shown in the hanab.live lobby
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "i32")]
+pub enum UserStatus {
+    Lobby,
+    /// Seated at a table that hasn't started
+    PreGame,
+    Playing,
+    Spectating,
+    Replay,
+    SharedReplay,
+    /// A status added to hanab.live after this was written
+    Unknown(i32),
+}
+
+impl From<i32> for UserStatus {
+    fn from(status: i32) -> Self {
+        match status {
+            0 => Self::Lobby,
+            1 => Self::PreGame,
+            2 => Self::Playing,
+            3 => Self::Spectating,
+            4 => Self::Replay,
+            5 => Self::SharedReplay,
+            _ => Self::Unknown(status),
+        }
+    }
+}
+
+#[command(name = "userList")]
+#[derive(Debug, Deserialize)]
+pub struct UserList(pub Vec<User>);
+
+#[command(name = "userLeft")]
+#[derive(Debug, Deserialize)]
+pub struct UserLeft {
+    #[serde(rename = "userID")]
+    pub user_id: UserID,
+}
+
+// Games
+
+/// Our past games, sent after login. The payload isn't used.
+#[command(name = "gameHistory")]
+#[derive(Debug, Deserialize)]
+pub struct GameHistory(pub IgnoredAny);
+
+// Chat
+
+#[serde_as]
+#[command(name = "chat")]
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub msg: String,
+    pub who: String,
+    #[serde_as(as = "NoneAsEmptyString")]
+    pub recipient: Option<String>,
+}
+
+#[command(name = "chatList")]
+#[derive(Debug, Deserialize)]
+pub struct ChatList {
+    pub list: Vec<Chat>,
+    pub unread: usize,
+}
+
+/// Someone is typing in a chat room. The payload isn't used.
+#[command(name = "chatTyping")]
+#[derive(Debug, Deserialize)]
+pub struct ChatTyping(pub IgnoredAny);
+
+// Actions on tables
+
+#[command(name = "joined")]
+#[derive(Debug, Deserialize)]
+pub struct Joined {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+#[command(name = "left")]
+#[derive(Debug, Deserialize)]
+pub struct Left {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+}
+
+// --- In game
+
+#[command(name = "init")]
+#[derive(Debug, Deserialize)]
+pub struct Init {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    pub player_names: Vec<String>,
+    // -1 when spectating
+    pub our_player_index: i32,
+    pub spectating: bool,
+    pub replay: bool,
+    pub options: GameOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameOptions {
+    pub num_players: usize,
+    pub variant_name: String,
+    pub timed: bool,
+    // Seconds
+    pub time_base: u64,
+    pub time_per_turn: u64,
+}
+
+#[command(name = "gameAction")]
+#[derive(Debug, Deserialize)]
+pub struct GameAction {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    pub action: GameActionType,
+}
+
+#[command(name = "gameActionList")]
+#[derive(Debug, Deserialize)]
+pub struct GameActionList {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    pub list: Vec<GameActionType>,
+}
+
+// suit_index and rank are -1 when the card is hidden from us
+#[derive(Debug, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum GameActionType {
+    Draw {
+        player_index: usize,
+        order: usize,
+        suit_index: i32,
+        rank: i32,
+    },
+    Play {
+        player_index: usize,
+        order: usize,
+        suit_index: i32,
+        rank: i32,
+    },
+    // A failed discard is a misplay
+    Discard {
+        player_index: usize,
+        order: usize,
+        suit_index: i32,
+        rank: i32,
+        failed: bool,
+    },
+    Clue {
+        giver: usize,
+        target: usize,
+        // Orders of the touched cards
+        list: Vec<usize>,
+        clue: ClueData,
+    },
+    Turn {
+        num: usize,
+        // -1 once the game is over
+        current_player_index: i32,
+    },
+    GameOver,
+    // strike, status, and others that the bot doesn't need
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClueData {
+    // 0 for color, 1 for rank
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub value: u8,
+}
+
+/// The ID a finished game was saved under. The payload isn't used.
+#[command(name = "databaseID")]
+#[derive(Debug, Deserialize)]
+pub struct DatabaseID(pub IgnoredAny);
+
+/// Which players at our table are connected. The payload isn't used.
+#[command(name = "connected")]
+#[derive(Debug, Deserialize)]
+pub struct Connected(pub IgnoredAny);
+
+#[command(name = "clock")]
+#[derive(Debug, Deserialize)]
+pub struct Clock {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    // Milliseconds left for each player. Negative when over time.
+    pub times: Vec<i64>,
+    // -1 once the game is over
+    pub active_player_index: i32,
+}
+
+#[command(name = "noteListPlayer")]
+#[derive(Debug, Deserialize)]
+pub struct NoteListPlayer {
+    #[serde(rename = "tableID")]
+    pub table_id: TableID,
+    // Our notes, indexed by card order
+    pub notes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServerMessage;
+
+    #[test]
+    fn parses_ignored_payloads() {
+        for text in [
+            r#"gameHistory [{"id":1,"numPlayers":2,"score":25}]"#,
+            r#"chatTyping {"tableID":1,"name":"alice","typing":true}"#,
+            r#"databaseID {"tableID":1,"databaseID":12345}"#,
+            r#"connected {"tableID":1,"list":[true,false]}"#,
+        ] {
+            let message = ServerMessage::parse(text);
+            assert!(message.is_ok(), "{text}: {message:?}");
+        }
+    }
+
+    #[test]
+    fn parses_unknown_commands() {
+        let message = ServerMessage::parse(r#"somethingNew {"a":1}"#).unwrap();
+        assert!(matches!(
+            message,
+            ServerMessage::Unknown { name, raw }
+                if name == "somethingNew" && raw["a"] == 1
+        ));
+    }
+
+    #[test]
+    fn rejects_mismatched_payloads() {
+        assert!(ServerMessage::parse(r#"tableGone {"tableID":"x"}"#).is_err());
+        assert!(ServerMessage::parse("nospace").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Client side of the hanab.live websocket protocol: message types, parsing,
+//! and logging in.
+
+#![warn(clippy::pedantic, clippy::nursery)]
+#![allow(clippy::wildcard_imports)]
+
+// So code generated by hanab-live-derive can name this crate from inside it
+extern crate self as hanab_live;
+
+pub mod auth;
+pub mod command;
+
+pub use auth::authenticate_and_get_cookie;
+pub use command::{Command, Dispatch, Parse, TableID, UserID};
+pub use url::Url;
+
+/// Base URL of the public hanab.live server. Servers are identified by their
+/// base URL, so a local development server works too.
+pub const DEFAULT_SERVER: &str = "https://hanab.live";
+
+/// The websocket endpoint of the server at `server`, e.g. `wss://hanab.live/ws`
+/// for `https://hanab.live`.
+///
+/// # Errors
+///
+/// If `server` isn't an http or https URL.
+pub fn websocket_url(server: &Url) -> eyre::Result<Url> {
+    let scheme = match server.scheme() {
+        "https" => "wss",
+        "http" => "ws",
+        scheme => eyre::bail!("{server} is not an http(s) URL, but {scheme}"),
+    };
+    let mut url = server.join("ws")?;
+    url.set_scheme(scheme).map_err(|()| {
+        eyre::eyre!("can't make a websocket URL from {server}")
+    })?;
+    Ok(url)
+}
+
+/// The login endpoint of the server at `server`.
+///
+/// # Errors
+///
+/// If `server` can't be a base URL.
+pub fn login_url(server: &Url) -> eyre::Result<Url> {
+    Ok(server.join("login")?)
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json;
+    pub use serde_with;
+
+    /// `==` for strings, usable in the constants `#[derive(Dispatch)]`
+    /// generates.
+    #[must_use]
+    pub const fn str_eq(a: &str, b: &str) -> bool {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use super::ChatCommand;
+
+// Who may give a bot chat commands. hanab.live usernames are compared
+// without regard to case.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Access {
+    // May use every command, whatever the other lists say
+    #[serde(default)]
+    owners: HashSet<String>,
+    // If set, only these users and the owners may use commands at all
+    allow: Option<HashSet<String>>,
+    // May not use any command
+    #[serde(default)]
+    deny: HashSet<String>,
+    // Who may use each command. Commands not listed are open to everyone
+    // allowed.
+    #[serde(default)]
+    commands: HashMap<CommandName, Who>,
+    // PMed instead of the default refusal
+    refusal: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandName {
+    Join,
+    Leave,
+    Create,
+    Start,
+    Why,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Who {
+    // Anyone let through by allow and deny
+    Allowed,
+    Owners,
+}
+
+impl Access {
+    fn is_owner(&self, user: &str) -> bool {
+        contains(&self.owners, user)
+    }
+
+    // Whether the user may use any command at all
+    pub fn allows(&self, user: &str) -> bool {
+        self.is_owner(user)
+            || (!contains(&self.deny, user)
+                && self.allow.as_ref().is_none_or(|x| contains(x, user)))
+    }
+
+    // Whether the user may use this command
+    pub fn permits(&self, user: &str, command: &ChatCommand) -> bool {
+        match self.commands.get(&command.name()) {
+            Some(Who::Owners) => self.is_owner(user),
+            Some(Who::Allowed) | None => self.allows(user),
+        }
+    }
+
+    // What to PM a user who isn't allowed, or isn't allowed this command
+    pub fn refusal(&self, command: Option<&ChatCommand>) -> String {
+        self.refusal.clone().unwrap_or_else(|| {
+            command.map_or_else(
+                || "Sorry, I don't take commands from you".to_owned(),
+                |command| {
+                    format!(
+                        "Sorry, only my owners may use /{}",
+                        command.name().as_str()
+                    )
+                },
+            )
+        })
+    }
+}
+
+impl CommandName {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Join => "join",
+            Self::Leave => "leave",
+            Self::Create => "create",
+            Self::Start => "start",
+            Self::Why => "why",
+        }
+    }
+}
+
+fn contains(users: &HashSet<String>, user: &str) -> bool {
+    users.iter().any(|x| x.eq_ignore_ascii_case(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn access(config: serde_json::Value) -> Access {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn open_by_default() {
+        let access = Access::default();
+        assert!(access.allows("anyone"));
+        assert!(access.permits("anyone", &ChatCommand::Start));
+        assert_eq!(
+            access.refusal(None),
+            "Sorry, I don't take commands from you"
+        );
+    }
+
+    #[test]
+    fn deny_overrides_allow() {
+        let access = access(json!({
+            "allow": ["alice", "bob"],
+            "deny": ["Bob"],
+        }));
+        assert!(access.allows("alice"));
+        assert!(access.allows("ALICE"));
+        assert!(!access.allows("bob"));
+        assert!(!access.allows("carol"));
+    }
+
+    #[test]
+    fn owners_bypass_the_lists() {
+        let access = access(json!({
+            "owners": ["alice"],
+            "allow": ["bob"],
+            "deny": ["alice"],
+            "commands": {"start": "owners"},
+        }));
+        assert!(access.allows("alice"));
+        assert!(access.permits("alice", &ChatCommand::Start));
+        assert!(!access.permits("bob", &ChatCommand::Start));
+    }
+
+    #[test]
+    fn commands_can_be_restricted() {
+        let access = access(json!({
+            "owners": ["alice"],
+            "commands": {"start": "owners", "leave": "allowed"},
+        }));
+        assert!(!access.permits("bob", &ChatCommand::Start));
+        assert!(access.permits("bob", &ChatCommand::Leave));
+        assert!(access.permits("bob", &ChatCommand::Why { turn: 1 }));
+        assert_eq!(
+            access.refusal(Some(&ChatCommand::Start)),
+            "Sorry, only my owners may use /start"
+        );
+    }
+
+    #[test]
+    fn custom_refusal() {
+        let access = access(json!({"deny": ["bob"], "refusal": "No."}));
+        assert_eq!(access.refusal(None), "No.");
+        assert_eq!(access.refusal(Some(&ChatCommand::Start)), "No.");
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let config = json!({"commands": {"dance": "owners"}});
+        assert!(serde_json::from_value::<Access>(config).is_err());
+    }
+}
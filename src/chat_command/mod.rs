@@ -1,3 +1,9 @@
+mod access;
+
+use clap::CommandFactory;
+
+pub use access::{Access, CommandName};
+
 #[derive(clap::Parser)]
 #[command(
     no_binary_name = true,
@@ -23,4 +29,41 @@ pub enum ChatCommand {
     },
     #[command(name = "/start")]
     Start,
+    // Explain the bot's move on a turn of its current or last game
+    #[command(name = "/why")]
+    Why { turn: usize },
+}
+
+impl ChatCommand {
+    pub const fn name(&self) -> CommandName {
+        match self {
+            Self::Join { .. } => CommandName::Join,
+            Self::Leave => CommandName::Leave,
+            Self::Create { .. } => CommandName::Create,
+            Self::Start => CommandName::Start,
+            Self::Why { .. } => CommandName::Why,
+        }
+    }
+
+    // "try /join, ... or /why <turn>", listing every command with its
+    // positional arguments
+    pub fn hint() -> String {
+        let usages: Vec<String> = Self::command()
+            .get_subcommands()
+            .map(|command| {
+                let args = command
+                    .get_positionals()
+                    .map(|arg| format!("<{}>", arg.get_id()));
+                std::iter::once(command.get_name().to_owned())
+                    .chain(args)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        match usages.split_last() {
+            Some((last, [])) => format!("try {last}"),
+            Some((last, rest)) => format!("try {} or {last}", rest.join(", ")),
+            None => String::new(),
+        }
+    }
 }
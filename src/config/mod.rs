@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, bail, eyre, WrapErr};
+use hanab_live::Url;
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+
+use crate::chat_command::Access;
+use crate::strategy::StrategyKind;
+
+#[cfg(test)]
+mod tests;
+
+// Bot settings come in layers: built-in defaults, then the config file's
+// top-level settings, then each bot's own section, then HANAB_* environment
+// variables, which override everything.
+//
+// The config file is TOML if its name ends in .toml, JSON otherwise. In
+// TOML:
+//
+//     default_bots = ["bot0", "bot1"]
+//     strategy = "basic"
+//
+//     [bots]
+//     # Shorthand for a section with only a password
+//     bot0 = "hunter2"
+//     bot1 = { password_env = "BOT1_PASSWORD", strategy = "random" }
+//     bot2 = { password_file = "/run/secrets/bot2", explain = true }
+//     bot3 = { password_prompt = true }
+//
+//     # Who may command the bots through chat. A bot's own access section
+//     # replaces this one.
+//     [access]
+//     owners = ["alice"]
+//     # Everyone but these, or with allow, only the users listed there
+//     deny = ["mallory"]
+//     refusal = "Sorry, ask alice"
+//     [access.commands]
+//     create = "owners"
+//     start = "allowed"
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    bots: HashMap<String, BotEntry>,
+    // Bots to run when no usernames are given, in order
+    #[serde(default)]
+    default_bots: Vec<String>,
+    // Directory of password files named after each bot, e.g. /run/secrets.
+    // Used for bots that don't say where their password is.
+    secrets_dir: Option<PathBuf>,
+    // Defaults for the settings in each bot's section
+    server: Option<String>,
+    strategy: Option<StrategyKind>,
+    explain: Option<bool>,
+    access: Option<Access>,
+}
+
+// One bot's section. Unset settings fall back to the top-level ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BotSection {
+    // Where the password comes from. At most one of these may be set.
+    password: Option<Password>,
+    // Name of an environment variable holding the password
+    password_env: Option<String>,
+    // File holding the password, e.g. a docker secret
+    password_file: Option<PathBuf>,
+    // Ask on the terminal at startup
+    #[serde(default)]
+    password_prompt: bool,
+    // Base URL of the hanab.live server to play on
+    server: Option<String>,
+    strategy: Option<StrategyKind>,
+    // Post reasoning to table chat after each game
+    explain: Option<bool>,
+    access: Option<Access>,
+}
+
+// A bot section, or just the bot's password
+#[derive(Debug)]
+struct BotEntry(BotSection);
+
+impl<'de> Deserialize<'de> for BotEntry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = BotEntry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a password or a bot section")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<BotEntry, E> {
+                Ok(BotEntry(BotSection {
+                    password: Some(Password(v.to_owned())),
+                    ..BotSection::default()
+                }))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<BotEntry, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                BotSection::deserialize(de::value::MapAccessDeserializer::new(
+                    map,
+                ))
+                .map(BotEntry)
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+// Settings from HANAB_* environment variables
+#[derive(Debug, Default)]
+struct EnvOverrides {
+    server: Option<String>,
+    strategy: Option<StrategyKind>,
+    explain: Option<bool>,
+    // Comma separated
+    default_bots: Option<Vec<String>>,
+    secrets_dir: Option<PathBuf>,
+}
+
+impl EnvOverrides {
+    fn read(problems: &mut Vec<String>) -> Self {
+        let var = |name| std::env::var(name).ok().filter(|x| !x.is_empty());
+        let strategy = var("HANAB_STRATEGY").and_then(|value| {
+            <StrategyKind as clap::ValueEnum>::from_str(&value, true)
+                .map_err(|e| problems.push(format!("HANAB_STRATEGY: {e}")))
+                .ok()
+        });
+        let explain = var("HANAB_EXPLAIN").and_then(|value| {
+            value
+                .parse()
+                .map_err(|_| {
+                    problems.push(format!(
+                        "HANAB_EXPLAIN: expected true or false, got {value:?}"
+                    ));
+                })
+                .ok()
+        });
+        Self {
+            server: var("HANAB_SERVER"),
+            strategy,
+            explain,
+            default_bots: var("HANAB_DEFAULT_BOTS").map(|value| {
+                value.split(',').map(|x| x.trim().to_owned()).collect()
+            }),
+            secrets_dir: var("HANAB_SECRETS_DIR").map(PathBuf::from),
+        }
+    }
+}
+
+// A bot's password. Debug doesn't show it, so it can't end up in logs.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Password(String);
+
+impl Password {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Password(..)")
+    }
+}
+
+enum PasswordSource {
+    Known(Password),
+    Prompt,
+}
+
+// Everything needed to run one bot
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    pub username: String,
+    pub password: Password,
+    pub server: Url,
+    pub strategy: StrategyKind,
+    pub explain: bool,
+    pub access: Access,
+}
+
+// A bot's settings other than its password
+struct Settings {
+    server: Url,
+    strategy: StrategyKind,
+    explain: bool,
+    access: Access,
+}
+
+// Which bots to run
+#[derive(Debug, Clone, Copy)]
+pub enum Selection<'a> {
+    Users(&'a [String]),
+    // The first n default bots
+    Default(usize),
+}
+
+#[derive(Debug)]
+pub struct Config {
+    path: PathBuf,
+    file: ConfigFile,
+    env: EnvOverrides,
+    // Found while loading, reported along with the rest by select
+    problems: Vec<String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("reading {}", path.display()))?;
+        let mut problems = Vec::new();
+        let env = EnvOverrides::read(&mut problems);
+        Self::parse(path, &text, env, problems)
+    }
+
+    fn parse(
+        path: &Path,
+        text: &str,
+        env: EnvOverrides,
+        problems: Vec<String>,
+    ) -> eyre::Result<Self> {
+        let file = if path.extension().is_some_and(|x| x == "toml") {
+            toml::from_str(text).map_err(eyre::Report::from)
+        } else {
+            serde_json::from_str(text).map_err(eyre::Report::from)
+        }
+        .wrap_err_with(|| format!("parsing {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            env,
+            problems,
+        })
+    }
+
+    // Settings for the selected bots. The whole config is checked, not just
+    // the selected bots, and every problem found is reported at once.
+    pub fn select(&self, selection: Selection) -> eyre::Result<Vec<BotConfig>> {
+        let mut problems = self.problems.clone();
+        let default_bots = self
+            .env
+            .default_bots
+            .as_ref()
+            .unwrap_or(&self.file.default_bots);
+        for username in default_bots {
+            if !self.file.bots.contains_key(username) {
+                problems.push(format!(
+                    "default bot {username:?} has no entry in bots"
+                ));
+            }
+        }
+        let usernames = match selection {
+            Selection::Users(users) => users,
+            Selection::Default(n) => {
+                if default_bots.len() < n {
+                    problems.push(format!(
+                        "{n} bots requested, but default_bots only has {}",
+                        default_bots.len()
+                    ));
+                }
+                &default_bots[..n.min(default_bots.len())]
+            }
+        };
+
+        let mut bots = Vec::new();
+        let mut selected = HashSet::new();
+        for username in usernames {
+            if !selected.insert(username) {
+                problems.push(format!("bot {username:?} is selected twice"));
+                continue;
+            }
+            match self.file.bots.get(username) {
+                Some(BotEntry(section)) => {
+                    let settings = self.resolve(section, &mut problems);
+                    let password =
+                        self.password(username, section, &mut problems);
+                    if let (Some(settings), Some(password)) =
+                        (settings, password)
+                    {
+                        bots.push((username, settings, password));
+                    }
+                }
+                None => {
+                    problems
+                        .push(format!("bot {username:?} has no entry in bots"));
+                }
+            }
+        }
+        for (username, BotEntry(section)) in &self.file.bots {
+            if !selected.contains(username) {
+                self.resolve(section, &mut problems);
+                check_password_sources(username, section, &mut problems);
+            }
+        }
+
+        if problems.is_empty() {
+            // Only ask for passwords once we know we can start
+            return bots
+                .into_iter()
+                .map(|(username, settings, password)| {
+                    let password = match password {
+                        PasswordSource::Known(password) => password,
+                        PasswordSource::Prompt => prompt_password(username)?,
+                    };
+                    Ok(BotConfig {
+                        username: username.clone(),
+                        password,
+                        server: settings.server,
+                        strategy: settings.strategy,
+                        explain: settings.explain,
+                        access: settings.access,
+                    })
+                })
+                .collect();
+        }
+        // A bad top-level setting shows up once per bot
+        let mut seen = HashSet::new();
+        problems.retain(|x| seen.insert(x.clone()));
+        let list: Vec<_> =
+            problems.iter().map(|x| format!("  - {x}")).collect();
+        bail!(
+            "invalid configuration in {}:\n{}",
+            self.path.display(),
+            list.join("\n")
+        )
+    }
+
+    // Apply the layers to one bot's section
+    fn resolve(
+        &self,
+        section: &BotSection,
+        problems: &mut Vec<String>,
+    ) -> Option<Settings> {
+        let server = self
+            .env
+            .server
+            .as_deref()
+            .or(section.server.as_deref())
+            .or(self.file.server.as_deref())
+            .unwrap_or(hanab_live::DEFAULT_SERVER);
+        let server = parse_server(server)
+            .map_err(|e| problems.push(format!("server {server:?}: {e}")))
+            .ok()?;
+        Some(Settings {
+            server,
+            strategy: self
+                .env
+                .strategy
+                .or(section.strategy)
+                .or(self.file.strategy)
+                .unwrap_or(StrategyKind::Basic),
+            explain: self
+                .env
+                .explain
+                .or(section.explain)
+                .or(self.file.explain)
+                .unwrap_or(false),
+            access: self.section_access(Some(section)),
+        })
+    }
+
+    // Who may command this bot through chat. Used by replay, which needs
+    // nothing else from the config.
+    pub fn access(&self, username: &str) -> Access {
+        self.section_access(self.file.bots.get(username).map(|x| &x.0))
+    }
+
+    fn section_access(&self, section: Option<&BotSection>) -> Access {
+        section
+            .and_then(|x| x.access.as_ref())
+            .or(self.file.access.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Look up a selected bot's password, short of prompting for it
+    fn password(
+        &self,
+        username: &str,
+        section: &BotSection,
+        problems: &mut Vec<String>,
+    ) -> Option<PasswordSource> {
+        if !check_password_sources(username, section, problems) {
+            return None;
+        }
+        if section.password_prompt {
+            return Some(PasswordSource::Prompt);
+        }
+        let secrets_dir = self
+            .env
+            .secrets_dir
+            .as_ref()
+            .or(self.file.secrets_dir.as_ref());
+        let password = match (
+            &section.password,
+            &section.password_env,
+            &section.password_file,
+            secrets_dir,
+        ) {
+            (Some(password), ..) => Ok(password.clone()),
+            (_, Some(var), ..) => std::env::var(var)
+                .ok()
+                .filter(|x| !x.is_empty())
+                .map(Password)
+                .ok_or_else(|| eyre!("environment variable {var} is not set")),
+            (_, _, Some(path), _) => read_password_file(path),
+            (_, _, _, Some(dir)) => read_password_file(&dir.join(username)),
+            _ => Err(eyre!("no password")),
+        };
+        password
+            .map(PasswordSource::Known)
+            .map_err(|e| problems.push(format!("bot {username:?}: {e:#}")))
+            .ok()
+    }
+}
+
+// At most one source may be given. Returns whether that holds.
+fn check_password_sources(
+    username: &str,
+    section: &BotSection,
+    problems: &mut Vec<String>,
+) -> bool {
+    let sources = [
+        section.password.is_some(),
+        section.password_env.is_some(),
+        section.password_file.is_some(),
+        section.password_prompt,
+    ];
+    if sources.into_iter().filter(|&x| x).count() > 1 {
+        problems.push(format!(
+            "bot {username:?}: set only one of password, password_env, \
+             password_file and password_prompt"
+        ));
+        return false;
+    }
+    true
+}
+
+// Docker secrets and the like usually end in a newline
+fn read_password_file(path: &Path) -> eyre::Result<Password> {
+    let text = std::fs::read_to_string(path).wrap_err_with(|| {
+        format!("reading password from {}", path.display())
+    })?;
+    let password = text.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        bail!("{} is empty", path.display());
+    }
+    Ok(Password(password.to_owned()))
+}
+
+fn prompt_password(username: &str) -> eyre::Result<Password> {
+    let password =
+        rpassword::prompt_password(format!("Password for {username}: "))
+            .wrap_err_with(|| format!("reading password for {username}"))?;
+    Ok(Password(password))
+}
+
+fn parse_server(server: &str) -> eyre::Result<Url> {
+    let url = Url::parse(server)?;
+    // Fails for anything we couldn't connect to
+    hanab_live::websocket_url(&url)?;
+    Ok(url)
+}
@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use color_eyre::eyre;
+
+use super::{
+    BotConfig, Config, EnvOverrides, Password, PasswordSource, Selection,
+};
+use crate::strategy::StrategyKind;
+
+fn parse_with(name: &str, text: &str, env: EnvOverrides) -> Config {
+    Config::parse(Path::new(name), text, env, Vec::new()).unwrap()
+}
+
+fn parse(name: &str, text: &str) -> Config {
+    parse_with(name, text, EnvOverrides::default())
+}
+
+fn error(result: eyre::Result<Vec<BotConfig>>) -> String {
+    result.expect_err("config should be invalid").to_string()
+}
+
+const JSON: &str = r#"{
+    "default_bots": ["bot0", "bot1"],
+    "strategy": "random",
+    "bots": {
+        "bot0": "hunter2",
+        "bot1": {"password": "swordfish", "strategy": "basic"}
+    }
+}"#;
+
+#[test]
+fn loads_json() {
+    let config = parse("config.json", JSON);
+    let bots = config.select(Selection::Default(2)).unwrap();
+    assert_eq!(bots[0].username, "bot0");
+    assert_eq!(bots[0].password.expose(), "hunter2");
+    // The top-level strategy, overridden by bot1's own
+    assert_eq!(bots[0].strategy, StrategyKind::Random);
+    assert_eq!(bots[1].strategy, StrategyKind::Basic);
+    assert_eq!(bots[1].server.as_str(), "https://hanab.live/");
+    assert!(!bots[1].explain);
+}
+
+#[test]
+fn loads_toml() {
+    let config = parse(
+        "config.toml",
+        r#"
+        default_bots = ["bot0"]
+        server = "http://localhost:8080"
+
+        [bots]
+        bot0 = { password = "hunter2", explain = true }
+
+        [access]
+        owners = ["alice"]
+        "#,
+    );
+    let bots = config.select(Selection::Default(1)).unwrap();
+    assert_eq!(bots[0].password.expose(), "hunter2");
+    assert_eq!(bots[0].server.as_str(), "http://localhost:8080/");
+    assert!(bots[0].explain);
+    assert!(bots[0].access.allows("alice"));
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let env = EnvOverrides {
+        strategy: Some(StrategyKind::Basic),
+        default_bots: Some(vec!["bot1".to_owned()]),
+        ..EnvOverrides::default()
+    };
+    let config = parse_with("config.json", JSON, env);
+    let bots = config.select(Selection::Default(1)).unwrap();
+    assert_eq!(bots[0].username, "bot1");
+    assert_eq!(bots[0].strategy, StrategyKind::Basic);
+}
+
+#[test]
+fn rejects_unknown_settings() {
+    let path = Path::new("config.json");
+    let text = r#"{"bots": {}, "stratgy": "basic"}"#;
+    let env = EnvOverrides::default();
+    assert!(Config::parse(path, text, env, Vec::new()).is_err());
+}
+
+#[test]
+fn rejects_duplicate_and_unknown_users() {
+    let config = parse("config.json", JSON);
+    let users = ["bot0".to_owned(), "bot0".to_owned()];
+    let e = error(config.select(Selection::Users(&users)));
+    assert!(e.contains(r#"bot "bot0" is selected twice"#), "{e}");
+    let e = error(config.select(Selection::Users(&["nobody".to_owned()])));
+    assert!(e.contains(r#"bot "nobody" has no entry in bots"#), "{e}");
+    let e = error(config.select(Selection::Default(3)));
+    assert!(e.contains("3 bots requested"), "{e}");
+}
+
+#[test]
+fn rejects_missing_password() {
+    let config = parse("config.json", r#"{"bots": {"bot0": {}}}"#);
+    let e = error(config.select(Selection::Users(&["bot0".to_owned()])));
+    assert!(e.contains(r#"bot "bot0": no password"#), "{e}");
+}
+
+// Every problem is reported at once, including ones in bots that weren't
+// selected
+#[test]
+fn reports_all_problems() {
+    let config = parse(
+        "config.json",
+        r#"{
+            "default_bots": ["bot0", "ghost"],
+            "bots": {
+                "bot0": {},
+                "bot1": {"password": "x", "server": "ftp://example.com"}
+            }
+        }"#,
+    );
+    let e = error(config.select(Selection::Default(1)));
+    assert!(e.contains(r#"default bot "ghost" has no entry"#), "{e}");
+    assert!(e.contains(r#"bot "bot0": no password"#), "{e}");
+    assert!(e.contains(r#"server "ftp://example.com""#), "{e}");
+}
+
+// The password bot0 ends up with
+fn password(config: &Config) -> eyre::Result<String> {
+    let bots = config.select(Selection::Users(&["bot0".to_owned()]))?;
+    Ok(bots[0].password.expose().to_owned())
+}
+
+fn json_password(bot0: &serde_json::Value) -> eyre::Result<String> {
+    let text = serde_json::json!({ "bots": { "bot0": bot0 } }).to_string();
+    password(&parse("config.json", &text))
+}
+
+#[test]
+fn password_inline() {
+    let password = json_password(&serde_json::json!("hunter2")).unwrap();
+    assert_eq!(password, "hunter2");
+    let section = serde_json::json!({ "password": "hunter2" });
+    assert_eq!(json_password(&section).unwrap(), "hunter2");
+}
+
+#[test]
+fn password_from_environment() {
+    // Unique to this test, since tests share the environment
+    let var = "HANAB_TEST_PASSWORD_FROM_ENVIRONMENT";
+    let section = serde_json::json!({ "password_env": var });
+    let e = json_password(&section).unwrap_err().to_string();
+    assert!(e.contains(&format!("{var} is not set")), "{e}");
+    std::env::set_var(var, "hunter2");
+    assert_eq!(json_password(&section).unwrap(), "hunter2");
+}
+
+#[test]
+fn password_from_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bot0");
+    // The trailing newline isn't part of it
+    std::fs::write(&path, "hunter2\n").unwrap();
+    let section = serde_json::json!({ "password_file": path });
+    assert_eq!(json_password(&section).unwrap(), "hunter2");
+    std::fs::write(&path, "\n").unwrap();
+    let e = json_password(&section).unwrap_err().to_string();
+    assert!(e.contains("is empty"), "{e}");
+}
+
+#[test]
+fn password_from_secrets_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("bot0"), "hunter2").unwrap();
+    let text = serde_json::json!({
+        "secrets_dir": dir.path(),
+        "bots": { "bot0": {} },
+    });
+    let config = parse("config.json", &text.to_string());
+    assert_eq!(password(&config).unwrap(), "hunter2");
+    // The environment variable takes precedence
+    let other = tempfile::tempdir().unwrap();
+    std::fs::write(other.path().join("bot0"), "swordfish").unwrap();
+    let env = EnvOverrides {
+        secrets_dir: Some(other.path().to_owned()),
+        ..EnvOverrides::default()
+    };
+    let config = parse_with("config.json", &text.to_string(), env);
+    assert_eq!(password(&config).unwrap(), "swordfish");
+}
+
+#[test]
+fn password_prompt() {
+    let text = r#"{"bots": {"bot0": {"password_prompt": true}}}"#;
+    let config = parse("config.json", text);
+    let section = &config.file.bots["bot0"].0;
+    let source = config.password("bot0", section, &mut Vec::new());
+    assert!(matches!(source, Some(PasswordSource::Prompt)));
+}
+
+#[test]
+fn password_from_one_source_only() {
+    let section = serde_json::json!({
+        "password": "hunter2",
+        "password_prompt": true,
+    });
+    let e = json_password(&section).unwrap_err().to_string();
+    assert!(e.contains("set only one of"), "{e}");
+}
+
+#[test]
+fn debug_hides_password() {
+    let password = Password("hunter2".to_owned());
+    assert!(!format!("{password:?}").contains("hunter2"));
+    let bots = parse("config.json", JSON).select(Selection::Default(1));
+    let debug = format!("{bots:?}");
+    assert!(debug.contains("bot0"), "{debug}");
+    assert!(!debug.contains("hunter2"), "{debug}");
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use clap::{CommandFactory, Parser};
+use futures::prelude::*;
+use hanab_live::command::client;
+use tokio::sync::mpsc;
+
+use crate::fleet::Fleet;
+use crate::hanabi_client::{Bot, Event, LobbySnapshot, Status};
+
+// What to tell a bot, after its name or "all"
+#[derive(clap::Parser)]
+#[command(
+    no_binary_name = true,
+    bin_name = "<BOT|all>",
+    disable_help_subcommand = true
+)]
+enum Order {
+    // Create a table. The bot is joined to it automatically.
+    Create {
+        name: Option<String>,
+        #[arg(short, long, default_value_t = 6)]
+        max_players: u8,
+        #[arg(short, long)]
+        password: Option<String>,
+    },
+    // Join a table by name
+    Join {
+        table: String,
+    },
+    // Keep going to this user's table
+    Follow {
+        user: String,
+    },
+    // Leave the current table
+    Leave,
+    // Start the current table
+    Start,
+    // Show what the bot is doing
+    Status,
+    // Disconnect the bot for good
+    Shutdown,
+}
+
+const HELP: &str = "\
+commands:
+  <BOT|all> <ORDER>  give an order to one bot or all of them
+  lobby              show the tables and who's at them
+  help               show this
+  quit               shut down every bot and exit";
+
+// Drive the bots by hand: orders are read from stdin, and what the bots see
+// is printed as it happens. Ends on quit or end of input, shutting down
+// every bot.
+pub async fn run(fleet: Fleet) {
+    for (i, bot) in fleet.bots().into_iter().enumerate() {
+        // Every bot sees the same lobby, so only the first reports it
+        tokio::spawn(print_events(bot, i == 0));
+    }
+    println!("{HELP}");
+    let mut lines = read_lines();
+    while let Some(line) = lines.recv().await {
+        let Some(words) = shlex::split(&line) else {
+            println!("unbalanced quotes");
+            continue;
+        };
+        match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {}
+            ["help"] => {
+                println!("{HELP}\n\n{}", Order::command().render_help());
+            }
+            ["quit" | "exit"] => break,
+            ["lobby"] => print_lobby(&fleet).await,
+            [target, ..] => match Order::try_parse_from(&words[1..]) {
+                Ok(order) => give(&fleet, target, &order),
+                Err(e) => println!("{}", e.render()),
+            },
+        }
+    }
+    fleet.shutdown_all();
+}
+
+// stdin is read on its own thread, since a blocking read in the runtime
+// would keep the process from exiting
+fn read_lines() -> mpsc::UnboundedReceiver<String> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn give(fleet: &Fleet, target: &str, order: &Order) {
+    let bots = if target == "all" {
+        fleet.bots()
+    } else if let Some(bot) = fleet.get(target) {
+        vec![bot]
+    } else {
+        println!("no bot named {target:?}");
+        return;
+    };
+    for bot in bots {
+        let request = match order {
+            Order::Create {
+                name,
+                max_players,
+                password,
+            } => bot
+                .create_table(client::TableCreate {
+                    name: name.clone(),
+                    max_players: *max_players,
+                    password: password.clone(),
+                })
+                .boxed(),
+            Order::Join { table } => bot.join_table(table.clone()).boxed(),
+            Order::Follow { user } => bot.follow_user(user.clone()).boxed(),
+            Order::Leave => bot.leave().boxed(),
+            Order::Start => bot.start().boxed(),
+            Order::Status => {
+                tokio::spawn(async move {
+                    match bot.status().await {
+                        Some(status) => {
+                            println!(
+                                "[{}] {}",
+                                bot.username,
+                                describe(&status)
+                            );
+                        }
+                        None => println!("[{}] not running", bot.username),
+                    }
+                });
+                continue;
+            }
+            Order::Shutdown => {
+                fleet.shutdown(&bot.username);
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            match request.await {
+                Ok(table_id) => {
+                    println!("[{}] done, at table {table_id}", bot.username);
+                }
+                Err(e) => println!("[{}] {e}", bot.username),
+            }
+        });
+    }
+}
+
+fn describe(status: &Status) -> String {
+    let mut text = status.lobby.to_owned();
+    if let Some(table_id) = status.table_id {
+        let _ = write!(text, " at table {table_id}");
+    }
+    if let Some(username) = &status.following {
+        let _ = write!(text, ", following {username}");
+    }
+    text
+}
+
+async fn print_lobby(fleet: &Fleet) {
+    let Some(bot) = fleet.bots().into_iter().next() else {
+        println!("no bots are running");
+        return;
+    };
+    let Some(LobbySnapshot { tables, idle_users }) = bot.lobby().await else {
+        println!("[{}] not running", bot.username);
+        return;
+    };
+    for table in tables {
+        println!(
+            "table {} {:?}: {}",
+            table.table_id,
+            table.name,
+            table.users.join(", ")
+        );
+    }
+    println!("in the lobby: {}", idle_users.join(", "));
+}
+
+async fn print_events(bot: Bot, lobby: bool) {
+    let name = &bot.username;
+    // To name users who leave
+    let mut users = HashMap::new();
+    let mut events = bot.subscribe().boxed();
+    while let Some(event) = events.next().await {
+        match event {
+            Event::UserUpdated {
+                user_id,
+                name: user,
+                table_id,
+            } if lobby => {
+                match table_id {
+                    Some(table_id) => {
+                        println!("[lobby] {user} is at table {table_id}");
+                    }
+                    None => println!("[lobby] {user} is in the lobby"),
+                }
+                users.insert(user_id, user);
+            }
+            Event::UserLeft { user_id } if lobby => {
+                if let Some(user) = users.remove(&user_id) {
+                    println!("[lobby] {user} left");
+                }
+            }
+            Event::TableUpdated {
+                table_id,
+                name: table_name,
+            } if lobby => {
+                println!("[lobby] table {table_id} {table_name:?}");
+            }
+            Event::TableGone { table_id } if lobby => {
+                println!("[lobby] table {table_id} closed");
+            }
+            Event::ChatReceived {
+                who,
+                msg,
+                recipient: None,
+            } if lobby => println!("[chat] {who}: {msg}"),
+            Event::ChatReceived {
+                who,
+                msg,
+                recipient: Some(recipient),
+            } => println!("[{name}] {who} to {recipient}: {msg}"),
+            Event::Joined { table_id } => {
+                println!("[{name}] joined table {table_id}");
+            }
+            Event::Left { table_id } => {
+                println!("[{name}] left table {table_id}");
+            }
+            Event::GameStarted {
+                table_id,
+                player_names,
+                our_index,
+            } => println!(
+                "[{name}] game started at table {table_id} with {}, \
+                 playing seat {}",
+                player_names.join(", "),
+                our_index + 1
+            ),
+            Event::ActionTaken {
+                table_id,
+                turn,
+                explanation,
+            } => {
+                println!(
+                    "[{name}] table {table_id} turn {turn}: {explanation}"
+                );
+            }
+            Event::GameOver { table_id, score } => {
+                println!(
+                    "[{name}] game at table {table_id} over, score {score}"
+                );
+            }
+            Event::Disconnected { error: None } => {
+                println!("[{name}] disconnected");
+            }
+            Event::Disconnected { error: Some(e) } => {
+                println!("[{name}] disconnected: {e}");
+            }
+            _ => {}
+        }
+    }
+}
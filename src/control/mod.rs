@@ -0,0 +1,290 @@
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, bail, WrapErr};
+use futures::prelude::*;
+use hanab_live::command::{client, TableID};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::fleet::Fleet;
+use crate::hanabi_client::{Bot, LobbyError, Status};
+
+// One request per line, each answered with one line, e.g.
+//   {"command":"list"}
+//   {"command":"join","bot":"bot2","table":"my table"}
+//   {"command":"follow","bot":"bot0","user":"alice"}
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "camelCase", deny_unknown_fields)]
+enum Request {
+    List,
+    #[serde(rename_all = "camelCase")]
+    Create {
+        bot: String,
+        name: Option<String>,
+        max_players: Option<u8>,
+        password: Option<String>,
+    },
+    Join {
+        bot: String,
+        table: String,
+    },
+    Follow {
+        bot: String,
+        user: String,
+    },
+    Leave {
+        bot: String,
+    },
+    Start {
+        bot: String,
+    },
+    Shutdown {
+        bot: String,
+    },
+}
+
+impl Request {
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Create { .. } => "create",
+            Self::Join { .. } => "join",
+            Self::Follow { .. } => "follow",
+            Self::Leave { .. } => "leave",
+            Self::Start { .. } => "start",
+            Self::Shutdown { .. } => "shutdown",
+        }
+    }
+}
+
+// {"ok":true} plus whatever the request returns, or {"ok":false,"error":..}
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    table_id: Option<TableID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bots: Option<Vec<Status>>,
+}
+
+impl Response {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Self::default()
+        }
+    }
+    fn error(error: &impl ToString) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::default()
+        }
+    }
+    fn table(result: Result<TableID, LobbyError>) -> Self {
+        match result {
+            Ok(table_id) => Self {
+                table_id: Some(table_id),
+                ..Self::ok()
+            },
+            Err(e) => Self::error(&e),
+        }
+    }
+}
+
+// Serve in the background, logging why if it stops
+pub fn spawn(path: PathBuf, fleet: Fleet) {
+    tokio::spawn(async move {
+        if let Err(e) = serve(&path, fleet).await {
+            tracing::error!("control socket stopped: {e:#}");
+        }
+    });
+}
+
+// Accept connections on a Unix socket until the process stops. Only our
+// user can connect. A socket left behind by an earlier run is replaced.
+async fn serve(path: &Path, fleet: Fleet) -> eyre::Result<()> {
+    let listener = bind_private(path)
+        .wrap_err_with(|| format!("binding {}", path.display()))?;
+    tracing::info!("control socket listening on {}", path.display());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let fleet = fleet.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &fleet).await {
+                tracing::warn!("control connection failed: {e}");
+            }
+        });
+    }
+}
+
+// The socket is bound in a directory only we can enter and moved into
+// place once it's private, so nobody can connect in between
+fn bind_private(path: &Path) -> eyre::Result<UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.file_type().is_socket() => {
+            bail!("{} exists and isn't a socket", path.display());
+        }
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(e.into());
+        }
+        _ => {}
+    }
+    let name = path
+        .file_name()
+        .ok_or_else(|| eyre::eyre!("not a file name"))?;
+    let mut dir_name = std::ffi::OsString::from(".");
+    dir_name.push(name);
+    dir_name.push(format!(".{}", std::process::id()));
+    let dir = path.with_file_name(dir_name);
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let inner = dir.join("socket");
+    let result = UnixListener::bind(&inner)
+        .map_err(eyre::Report::from)
+        .and_then(|listener| {
+            std::fs::set_permissions(
+                &inner,
+                std::fs::Permissions::from_mode(0o600),
+            )?;
+            std::fs::rename(&inner, path)?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&inner);
+    let _ = std::fs::remove_dir(&dir);
+    result
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    fleet: &Fleet,
+) -> eyre::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => handle(request, fleet).await,
+            Err(e) => Response::error(&format!("bad request: {e}")),
+        };
+        let mut text = serde_json::to_string(&response)?;
+        text.push('\n');
+        writer.write_all(text.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+// Only the kind of request is recorded, since a request can hold a password
+#[tracing::instrument(skip_all, fields(command = request.kind()))]
+async fn handle(request: Request, fleet: &Fleet) -> Response {
+    let bot = |username: &str| {
+        fleet.get(username).ok_or_else(|| {
+            Response::error(&format!("no bot named {username:?}"))
+        })
+    };
+    let result = match request {
+        Request::List => return list(fleet).await,
+        Request::Create {
+            bot: username,
+            name,
+            max_players,
+            password,
+        } => bot(&username).map(|bot| {
+            let default = client::TableCreate::default();
+            bot.create_table(client::TableCreate {
+                name,
+                max_players: max_players.unwrap_or(default.max_players),
+                password,
+            })
+            .boxed()
+        }),
+        Request::Join {
+            bot: username,
+            table,
+        } => bot(&username).map(|bot| bot.join_table(table).boxed()),
+        // The first join might not come for a long time, so don't wait
+        // for it
+        Request::Follow {
+            bot: username,
+            user,
+        } => {
+            return bot(&username).map_or_else(
+                |e| e,
+                |bot| {
+                    drop(bot.follow_user(user));
+                    Response::ok()
+                },
+            );
+        }
+        Request::Leave { bot: username } => {
+            bot(&username).map(|bot| bot.leave().boxed())
+        }
+        Request::Start { bot: username } => {
+            bot(&username).map(|bot| bot.start().boxed())
+        }
+        Request::Shutdown { bot: username } => {
+            return if fleet.shutdown(&username) {
+                Response::ok()
+            } else {
+                Response::error(&format!("no bot named {username:?}"))
+            };
+        }
+    };
+    match result {
+        Ok(request) => Response::table(request.await),
+        Err(response) => response,
+    }
+}
+
+async fn list(fleet: &Fleet) -> Response {
+    let statuses = fleet.bots().into_iter().map(|bot| async move {
+        bot.status().await.unwrap_or_else(|| disconnected(bot))
+    });
+    Response {
+        bots: Some(future::join_all(statuses).await),
+        ..Response::ok()
+    }
+}
+
+// A daemon bot between connections
+fn disconnected(bot: Bot) -> Status {
+    Status {
+        username: bot.username,
+        lobby: "disconnected",
+        table_id: None,
+        following: None,
+        daemon: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn socket_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        drop(bind_private(&path).unwrap());
+        // A stale socket is replaced
+        let _listener = bind_private(&path).unwrap();
+        let metadata = std::fs::symlink_metadata(&path).unwrap();
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn leaves_other_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, "{}").unwrap();
+        assert!(bind_private(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use futures::stream::FuturesUnordered;
+use tracing::Instrument;
+
+use crate::config::BotConfig;
+use crate::fleet::Fleet;
+use crate::hanabi_client::{Bot, ConnectOptions};
+use crate::logging;
+use crate::metrics::BotMetrics;
+
+// Waits between reconnects, doubling after each failure
+const MIN_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_mins(5);
+
+// Keep every bot connected and in the lobby, where anyone can PM it /join to
+// have it join their table. After each game it goes back to the lobby.
+// Runs until every bot is shut down through the fleet. With explain, every
+// bot posts its reasoning after each game, whatever its config says.
+pub async fn run(
+    bots: Vec<BotConfig>,
+    options: ConnectOptions,
+    explain: bool,
+    fleet: Fleet,
+) {
+    let mut tasks: FuturesUnordered<_> = bots
+        .into_iter()
+        .enumerate()
+        .map(|(i, config)| {
+            let (options, fleet) = (options.clone(), fleet.clone());
+            let span = logging::bot_span(i, &config.username);
+            tokio::spawn(
+                serve(config, options, explain, fleet).instrument(span),
+            )
+        })
+        .collect();
+    while tasks.next().await.is_some() {}
+}
+
+async fn serve(
+    config: BotConfig,
+    options: ConnectOptions,
+    explain: bool,
+    fleet: Fleet,
+) {
+    let username = &config.username;
+    let mut retry = MIN_RETRY;
+    let mut connected_before = false;
+    while !fleet.is_stopped(username) {
+        match Bot::new(&config, &options).await {
+            Ok((bot, future)) => {
+                bot.daemon(true);
+                bot.explain(explain || config.explain);
+                fleet.insert(bot);
+                if connected_before {
+                    BotMetrics::new(username).reconnected();
+                }
+                connected_before = true;
+                tracing::info!("waiting for invitations");
+                retry = MIN_RETRY;
+                match future.await {
+                    Ok(()) => tracing::warn!("disconnected"),
+                    Err(e) => tracing::error!("disconnected: {e}"),
+                }
+                if fleet.is_stopped(username) {
+                    break;
+                }
+            }
+            Err(e) => tracing::error!("couldn't connect: {e}"),
+        }
+        tracing::info!("reconnecting in {}s", retry.as_secs());
+        tokio::select! {
+            () = tokio::time::sleep(retry) => {}
+            () = fleet.closing() => {}
+        }
+        retry = (retry * 2).min(MAX_RETRY);
+    }
+    tracing::info!("shut down");
+}
@@ -0,0 +1,127 @@
+use color_eyre::eyre::{self, eyre, WrapErr};
+
+use crate::game::{EndCondition, GameState, Variant};
+use crate::strategy::{self, StrategyKind};
+
+// Run strategies over many simulated games and report how they score
+#[derive(Debug, clap::Args)]
+pub struct EvalArgs {
+    // Strategy used by every seat
+    #[arg(short, long, value_enum, default_value_t = StrategyKind::Basic)]
+    strategy: StrategyKind,
+    // Strategy per seat, e.g. "basic,random". Repeats if there are more
+    // seats than strategies. Overrides --strategy.
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "STRATEGY")]
+    seats: Vec<StrategyKind>,
+    // Number of seeds to play for each player count and variant
+    #[arg(long, default_value_t = 1000)]
+    seeds: u64,
+    #[arg(long, default_value_t = 0)]
+    first_seed: u64,
+    #[arg(long, value_delimiter = ',', default_values_t = [2, 3, 4, 5])]
+    players: Vec<usize>,
+    // hanab.live variant names
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = [String::from("No Variant")]
+    )]
+    variants: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Stats {
+    games: u32,
+    total_score: u64,
+    perfect: u32,
+    strikeouts: u32,
+}
+
+impl Stats {
+    fn record(&mut self, state: &GameState) {
+        self.games += 1;
+        self.total_score += u64::from(state.score());
+        if state.score() == state.variant().max_score() {
+            self.perfect += 1;
+        }
+        if state.end() == Some(EndCondition::Strikeout) {
+            self.strikeouts += 1;
+        }
+    }
+    const fn add(&mut self, other: Self) {
+        self.games += other.games;
+        self.total_score += other.total_score;
+        self.perfect += other.perfect;
+        self.strikeouts += other.strikeouts;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    fn rate(&self, count: u64) -> f64 {
+        count as f64 / f64::from(self.games.max(1))
+    }
+    fn row(&self, variant: &str, players: &str) -> String {
+        format!(
+            "{variant:<20} {players:>7} {:>6} {:>10.2} {:>7.1}% {:>9.1}%",
+            self.games,
+            self.rate(self.total_score),
+            100.0 * self.rate(self.perfect.into()),
+            100.0 * self.rate(self.strikeouts.into()),
+        )
+    }
+}
+
+pub fn run(args: &EvalArgs) -> eyre::Result<()> {
+    let variants = args
+        .variants
+        .iter()
+        .map(|name| {
+            Variant::from_name(name)
+                .ok_or_else(|| eyre!("unknown variant {name:?}"))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let end_seed =
+        args.first_seed.checked_add(args.seeds).ok_or_else(|| {
+            eyre!("--first-seed plus --seeds must be at most {}", u64::MAX)
+        })?;
+    let seat_strategies = if args.seats.is_empty() {
+        vec![args.strategy]
+    } else {
+        args.seats.clone()
+    };
+
+    println!("seats: {seat_strategies:?}");
+    println!(
+        "{:<20} {:>7} {:>6} {:>10} {:>8} {:>10}",
+        "variant", "players", "games", "mean score", "perfect", "strikeout"
+    );
+    let mut overall = Stats::default();
+    for variant in variants {
+        let mut variant_stats = Stats::default();
+        for &num_players in &args.players {
+            let mut stats = Stats::default();
+            for seed in args.first_seed..end_seed {
+                let mut strategies: Vec<_> = (0..num_players)
+                    .map(|seat| {
+                        let strategy_seed =
+                            seed.wrapping_mul(31).wrapping_add(seat as u64);
+                        seat_strategies[seat % seat_strategies.len()]
+                            .build(strategy_seed)
+                    })
+                    .collect();
+                let game = strategy::play_game(variant, seed, &mut strategies)
+                    .wrap_err_with(|| {
+                        format!(
+                            "{:?}, {num_players} players, seed {seed}",
+                            variant.name
+                        )
+                    })?;
+                stats.record(&game);
+            }
+            println!("{}", stats.row(variant.name, &num_players.to_string()));
+            variant_stats.add(stats);
+        }
+        println!("{}", variant_stats.row(variant.name, "all"));
+        overall.add(variant_stats);
+    }
+    println!("{}", overall.row("overall", "all"));
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+
+use crate::hanabi_client::Bot;
+
+// The running bots by username, for whatever steers them while they run.
+// Clones share the same bots.
+#[derive(Debug, Clone)]
+pub struct Fleet {
+    inner: Arc<Mutex<Inner>>,
+    // PMed by each bot that's shut down while at a table
+    farewell: Option<String>,
+    // Set once every bot is shut down, including ones still connecting
+    closing: Arc<watch::Sender<bool>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    bots: BTreeMap<String, Bot>,
+    // Shut down for good, so the daemon doesn't reconnect them
+    stopped: HashSet<String>,
+}
+
+impl Fleet {
+    pub fn new(farewell: Option<String>) -> Self {
+        Self {
+            inner: Arc::default(),
+            farewell,
+            closing: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    // Replaces the old handle for the same username, e.g. after a reconnect.
+    // A bot that joins after shutdown_all is shut down right away.
+    pub fn insert(&self, bot: Bot) {
+        // Checked under the lock, so shutdown_all either sees this bot or
+        // this sees it was called
+        let mut inner = self.inner.lock().unwrap();
+        if !*self.closing.borrow() {
+            inner.bots.insert(bot.username.clone(), bot);
+            return;
+        }
+        inner.stopped.insert(bot.username.clone());
+        drop(inner);
+        bot.shutdown(self.farewell.clone());
+    }
+
+    pub fn get(&self, username: &str) -> Option<Bot> {
+        self.inner.lock().unwrap().bots.get(username).cloned()
+    }
+
+    // Sorted by username
+    pub fn bots(&self) -> Vec<Bot> {
+        self.inner.lock().unwrap().bots.values().cloned().collect()
+    }
+
+    // Stop a bot for good. Returns whether there was such a bot.
+    pub fn shutdown(&self, username: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(bot) = inner.bots.remove(username) else {
+            return false;
+        };
+        inner.stopped.insert(bot.username.clone());
+        drop(inner);
+        bot.shutdown(self.farewell.clone());
+        true
+    }
+
+    // Stop every bot for good, and any that connect later
+    pub fn shutdown_all(&self) {
+        self.closing.send_replace(true);
+        for bot in self.bots() {
+            self.shutdown(&bot.username);
+        }
+    }
+
+    pub fn is_stopped(&self, username: &str) -> bool {
+        *self.closing.borrow()
+            || self.inner.lock().unwrap().stopped.contains(username)
+    }
+
+    // Resolves once shutdown_all is called
+    pub async fn closing(&self) {
+        let _ = self.closing.subscribe().wait_for(|x| *x).await;
+    }
+}
@@ -0,0 +1,80 @@
+use super::{Card, Variant};
+
+// Set of card identities, one bit per (suit, rank)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Possibilities(u64);
+
+impl Possibilities {
+    pub const EMPTY: Self = Self(0);
+
+    pub fn all(variant: &Variant) -> Self {
+        (0..variant.suits.len())
+            .flat_map(|suit| (1..=5).map(move |rank| Card { suit, rank }))
+            .fold(Self::EMPTY, Self::with)
+    }
+    const fn bit(card: Card) -> u64 {
+        1 << (card.suit * 5 + card.rank as usize - 1)
+    }
+    #[must_use]
+    pub const fn with(self, card: Card) -> Self {
+        Self(self.0 | Self::bit(card))
+    }
+    pub const fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    #[must_use]
+    pub fn filter(self, mut f: impl FnMut(Card) -> bool) -> Self {
+        self.iter()
+            .filter(|&card| f(card))
+            .fold(Self::EMPTY, Self::with)
+    }
+    pub fn iter(self) -> impl Iterator<Item = Card> {
+        (0..64)
+            .filter(move |i| self.0 & (1 << i) != 0)
+            .map(|i: usize| Card {
+                suit: i / 5,
+                rank: u8::try_from(i % 5 + 1).unwrap(),
+            })
+    }
+    // The identity of the card, if only one remains
+    pub fn known(self) -> Option<Card> {
+        (self.len() == 1).then(|| self.iter().next().unwrap())
+    }
+}
+
+// What everyone at the table knows about a card from the clues it received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardKnowledge {
+    pub possible: Possibilities,
+    pub clued: bool,
+}
+
+impl CardKnowledge {
+    pub fn new(variant: &Variant) -> Self {
+        Self {
+            possible: Possibilities::all(variant),
+            clued: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn narrows_to_known_card() {
+        let variant = Variant::from_name("No Variant").unwrap();
+        let all = Possibilities::all(variant);
+        assert_eq!(all.len(), 25);
+        let red = all.filter(|card| card.suit == 0);
+        assert_eq!(red.len(), 5);
+        assert_eq!(red.known(), None);
+        let red_three = red.filter(|card| card.rank == 3);
+        assert_eq!(red_three.known(), Some(Card { suit: 0, rank: 3 }));
+        assert!(red_three.filter(|card| card.rank == 4).is_empty());
+    }
+}
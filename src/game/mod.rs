@@ -0,0 +1,667 @@
+use std::fmt;
+
+use color_eyre::eyre::{self, bail, eyre};
+use serde::{Deserialize, Serialize};
+
+mod knowledge;
+mod variant;
+
+pub use knowledge::{CardKnowledge, Possibilities};
+pub use variant::Variant;
+
+pub const MAX_CLUE_TOKENS: u8 = 8;
+pub const MAX_STRIKES: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Card {
+    // Index into Variant::suits
+    pub suit: usize,
+    pub rank: u8,
+}
+
+// Cards are identified by the order they were drawn in, like on hanab.live
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardOrder(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clue {
+    // Index into Variant::clue_colors
+    Color(usize),
+    Rank(u8),
+}
+
+impl Clue {
+    pub fn touches(self, variant: &Variant, card: Card) -> bool {
+        match self {
+            Self::Color(color) => {
+                variant.suits[card.suit].rainbow
+                    || variant.clue_colors().nth(color) == Some(card.suit)
+            }
+            Self::Rank(rank) => card.rank == rank,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Play(CardOrder),
+    Discard(CardOrder),
+    Clue { target: usize, clue: Clue },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndCondition {
+    Normal,
+    Strikeout,
+}
+
+// Small deterministic PRNG (SplitMix64), so simulations can be reproduced
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    pub const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    // Uniform in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        usize::try_from(self.next_u64() % n as u64).unwrap()
+    }
+}
+
+pub fn shuffled_deck(variant: &Variant, seed: u64) -> Vec<Card> {
+    let mut deck: Vec<Card> = (0..variant.suits.len())
+        .flat_map(|suit| {
+            (1..=5).flat_map(move |rank| {
+                let copies = variant.copies(suit, rank);
+                (0..copies).map(move |_| Card { suit, rank })
+            })
+        })
+        .collect();
+    // Fisher-Yates
+    let mut rng = Rng::new(seed);
+    for i in (1..deck.len()).rev() {
+        deck.swap(i, rng.below(i + 1));
+    }
+    deck
+}
+
+pub const fn hand_size(num_players: usize) -> usize {
+    match num_players {
+        0..=3 => 5,
+        4 | 5 => 4,
+        _ => 3,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameState {
+    variant: &'static Variant,
+    num_players: usize,
+    // Card identities by order. None if not known to us, e.g. our own hand
+    // when tracking a live game.
+    cards: Vec<Option<Card>>,
+    knowledge: Vec<CardKnowledge>,
+    // The draw pile, top first. Contains the whole deck in simulations.
+    draw_pile: Vec<Option<Card>>,
+    // Each hand has its newest card first, like on hanab.live
+    hands: Vec<Vec<CardOrder>>,
+    play_stacks: Vec<u8>,
+    discard_pile: Vec<CardOrder>,
+    clue_tokens: u8,
+    strikes: u8,
+    turn: usize,
+    current_player: usize,
+    // Set once the last card is drawn
+    turns_left: Option<usize>,
+    end: Option<EndCondition>,
+}
+
+impl GameState {
+    pub fn new(
+        variant: &'static Variant,
+        num_players: usize,
+        deck: Vec<Option<Card>>,
+    ) -> eyre::Result<Self> {
+        if !(2..=6).contains(&num_players) {
+            bail!("unsupported number of players: {num_players}");
+        }
+        let mut state = Self {
+            variant,
+            num_players,
+            cards: Vec::new(),
+            knowledge: Vec::new(),
+            draw_pile: deck,
+            hands: vec![Vec::new(); num_players],
+            play_stacks: vec![0; variant.suits.len()],
+            discard_pile: Vec::new(),
+            clue_tokens: MAX_CLUE_TOKENS,
+            strikes: 0,
+            turn: 0,
+            current_player: 0,
+            turns_left: None,
+            end: None,
+        };
+        // hanab.live deals each player's whole hand in turn
+        for player in 0..num_players {
+            for _ in 0..hand_size(num_players) {
+                state.draw(player);
+            }
+        }
+        Ok(state)
+    }
+
+    // Start a simulated game from a seed
+    pub fn from_seed(
+        variant: &'static Variant,
+        num_players: usize,
+        seed: u64,
+    ) -> eyre::Result<Self> {
+        let deck = shuffled_deck(variant, seed).into_iter().map(Some);
+        Self::new(variant, num_players, deck.collect())
+    }
+
+    pub const fn variant(&self) -> &'static Variant {
+        self.variant
+    }
+    pub fn hand(&self, player: usize) -> &[CardOrder] {
+        &self.hands[player]
+    }
+    pub fn card(&self, order: CardOrder) -> Option<Card> {
+        self.cards.get(order.0).copied().flatten()
+    }
+    pub const fn strikes(&self) -> u8 {
+        self.strikes
+    }
+    pub const fn turn(&self) -> usize {
+        self.turn
+    }
+    pub const fn current_player(&self) -> usize {
+        self.current_player
+    }
+    pub const fn end(&self) -> Option<EndCondition> {
+        self.end
+    }
+
+    pub fn score(&self) -> u32 {
+        match self.end {
+            // hanab.live scores a strikeout as 0
+            Some(EndCondition::Strikeout) => 0,
+            _ => self.play_stacks.iter().map(|&x| u32::from(x)).sum(),
+        }
+    }
+
+    pub fn is_playable(&self, card: Card) -> bool {
+        card.rank == self.play_stacks[card.suit] + 1
+    }
+
+    // Highest rank that can still be played on a suit, given the discards
+    pub fn max_rank(&self, suit: usize) -> u8 {
+        (self.play_stacks[suit] + 1..=5)
+            .find(|&rank| {
+                let discarded = self
+                    .discard_pile
+                    .iter()
+                    .filter(|&&order| {
+                        self.card(order) == Some(Card { suit, rank })
+                    })
+                    .count();
+                discarded == usize::from(self.variant.copies(suit, rank))
+            })
+            .map_or(5, |rank| rank - 1)
+    }
+
+    pub fn is_trash(&self, card: Card) -> bool {
+        card.rank <= self.play_stacks[card.suit]
+            || card.rank > self.max_rank(card.suit)
+    }
+
+    // The last copy of a card that is still needed
+    pub fn is_critical(&self, card: Card) -> bool {
+        if self.is_trash(card) {
+            return false;
+        }
+        let discarded = self
+            .discard_pile
+            .iter()
+            .filter(|&&order| self.card(order) == Some(card))
+            .count();
+        discarded + 1 == usize::from(self.variant.copies(card.suit, card.rank))
+    }
+
+    // Record the identity of a card, e.g. when it is played from our hand
+    pub fn reveal(&mut self, order: CardOrder, card: Card) {
+        self.cards[order.0] = Some(card);
+    }
+
+    pub fn apply(&mut self, action: Action) -> eyre::Result<()> {
+        if self.end.is_some() {
+            bail!("game is already over");
+        }
+        let player = self.current_player;
+        match action {
+            Action::Play(order) => {
+                let card = self.remove_from_hand(player, order)?;
+                if self.is_playable(card) {
+                    self.play_stacks[card.suit] = card.rank;
+                    if card.rank == 5 && self.clue_tokens < MAX_CLUE_TOKENS {
+                        self.clue_tokens += 1;
+                    }
+                } else {
+                    self.strikes += 1;
+                    self.discard_pile.push(order);
+                }
+                self.draw(player);
+            }
+            Action::Discard(order) => {
+                if self.clue_tokens == MAX_CLUE_TOKENS {
+                    bail!("cannot discard at {MAX_CLUE_TOKENS} clue tokens");
+                }
+                self.remove_from_hand(player, order)?;
+                self.discard_pile.push(order);
+                self.clue_tokens += 1;
+                self.draw(player);
+            }
+            Action::Clue { target, clue } => {
+                if target >= self.num_players {
+                    bail!("there is no player {target}");
+                }
+                let touched = self.touched(target, clue);
+                return self.apply_clue(target, clue, &touched);
+            }
+        }
+        self.end_turn();
+        Ok(())
+    }
+
+    // Clues where we can't tell which cards are touched, e.g. clues to our
+    // own hand in a live game
+    pub fn apply_clue(
+        &mut self,
+        target: usize,
+        clue: Clue,
+        touched: &[CardOrder],
+    ) -> eyre::Result<()> {
+        if self.end.is_some() {
+            bail!("game is already over");
+        }
+        self.clue(self.current_player, target, clue, touched)?;
+        self.end_turn();
+        Ok(())
+    }
+
+    fn end_turn(&mut self) {
+        let player = self.current_player;
+        self.turn += 1;
+        self.current_player = (player + 1) % self.num_players;
+        if let Some(turns_left) = &mut self.turns_left {
+            *turns_left -= 1;
+        }
+        if self.strikes == MAX_STRIKES {
+            self.end = Some(EndCondition::Strikeout);
+        } else if self.turns_left == Some(0)
+            || self.score() == self.variant.max_score()
+        {
+            self.end = Some(EndCondition::Normal);
+        }
+    }
+
+    fn draw(&mut self, player: usize) {
+        if self.draw_pile.is_empty() {
+            return;
+        }
+        let card = self.draw_pile.remove(0);
+        let order = CardOrder(self.cards.len());
+        self.cards.push(card);
+        self.knowledge.push(CardKnowledge::new(self.variant));
+        self.hands[player].insert(0, order);
+        if self.draw_pile.is_empty() {
+            // Everyone, including this player, gets one more turn
+            self.turns_left = Some(self.num_players + 1);
+        }
+    }
+
+    fn remove_from_hand(
+        &mut self,
+        player: usize,
+        order: CardOrder,
+    ) -> eyre::Result<Card> {
+        let hand = &mut self.hands[player];
+        let slot = hand.iter().position(|&x| x == order).ok_or_else(|| {
+            eyre!("card {order:?} is not in player {player}'s hand")
+        })?;
+        let card = self
+            .card(order)
+            .ok_or_else(|| eyre!("identity of card {order:?} is unknown"))?;
+        self.hands[player].remove(slot);
+        Ok(card)
+    }
+
+    fn clue(
+        &mut self,
+        player: usize,
+        target: usize,
+        clue: Clue,
+        touched: &[CardOrder],
+    ) -> eyre::Result<()> {
+        if self.clue_tokens == 0 {
+            bail!("no clue tokens left");
+        }
+        if target == player || target >= self.num_players {
+            bail!("player {player} cannot clue player {target}");
+        }
+        if touched.is_empty() {
+            bail!("clue {clue:?} touches no cards in player {target}'s hand");
+        }
+        for &order in &self.hands[target] {
+            let knowledge = &mut self.knowledge[order.0];
+            let is_touched = touched.contains(&order);
+            knowledge.possible = knowledge
+                .possible
+                .filter(|card| clue.touches(self.variant, card) == is_touched);
+            knowledge.clued |= is_touched;
+        }
+        self.clue_tokens -= 1;
+        Ok(())
+    }
+
+    // Cards in a player's hand that a clue would touch. Cards we can't see
+    // are never considered touched.
+    pub fn touched(&self, target: usize, clue: Clue) -> Vec<CardOrder> {
+        self.hands[target]
+            .iter()
+            .copied()
+            .filter(|&order| {
+                self.card(order)
+                    .is_some_and(|card| clue.touches(self.variant, card))
+            })
+            .collect()
+    }
+}
+
+// A player's view of the game: everything except their own cards
+#[derive(Clone, Copy)]
+pub struct PlayerView<'a> {
+    state: &'a GameState,
+    seat: usize,
+}
+
+impl<'a> PlayerView<'a> {
+    pub const fn new(state: &'a GameState, seat: usize) -> Self {
+        Self { state, seat }
+    }
+    pub const fn seat(&self) -> usize {
+        self.seat
+    }
+    // Cards in other players' hands, or cards that left a hand
+    pub fn card(&self, order: CardOrder) -> Option<Card> {
+        if self.state.hand(self.seat).contains(&order) {
+            return None;
+        }
+        self.state.card(order)
+    }
+    // Other players' cards that a clue would touch
+    pub fn touched(&self, target: usize, clue: Clue) -> Vec<CardOrder> {
+        if target == self.seat {
+            return Vec::new();
+        }
+        self.state.touched(target, clue)
+    }
+    // What this player can deduce about a card: the clues it received, minus
+    // identities whose every copy is visible elsewhere
+    pub fn possible(&self, order: CardOrder) -> Possibilities {
+        if let Some(card) = self.card(order) {
+            return Possibilities::EMPTY.with(card);
+        }
+        let public = self.public();
+        let variant = public.variant();
+        public.knowledge(order).possible.filter(|card| {
+            self.visible_copies(card) < variant.copies(card.suit, card.rank)
+        })
+    }
+    fn visible_copies(&self, card: Card) -> u8 {
+        let played = u8::from(card.rank <= self.state.play_stacks[card.suit]);
+        let others = (0..self.state.num_players)
+            .filter(|&player| player != self.seat)
+            .flat_map(|player| self.state.hand(player))
+            .chain(&self.state.discard_pile)
+            .filter(|&&order| self.state.card(order) == Some(card))
+            .count();
+        played + u8::try_from(others).unwrap()
+    }
+    pub fn legal_actions(&self) -> Vec<Action> {
+        let public = self.public();
+        let hand = public.hand(self.seat);
+        let mut actions: Vec<Action> =
+            hand.iter().map(|&order| Action::Play(order)).collect();
+        if public.clue_tokens() < MAX_CLUE_TOKENS {
+            actions.extend(hand.iter().map(|&order| Action::Discard(order)));
+        }
+        if public.clue_tokens() > 0 {
+            let variant = public.variant();
+            let clues: Vec<Clue> = (0..variant.clue_colors().count())
+                .map(Clue::Color)
+                .chain((1..=5).map(Clue::Rank))
+                .collect();
+            for target in 0..public.num_players() {
+                for &clue in &clues {
+                    if !self.touched(target, clue).is_empty() {
+                        actions.push(Action::Clue { target, clue });
+                    }
+                }
+            }
+        }
+        actions
+    }
+    // Public information. Card identities go through PlayerView::card.
+    pub const fn public(&self) -> PublicState<'a> {
+        PublicState(self.state)
+    }
+}
+
+impl fmt::Debug for PlayerView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlayerView")
+            .field("seat", &self.seat)
+            .field("turn", &self.state.turn)
+            .finish_non_exhaustive()
+    }
+}
+
+// Game information visible to every player
+#[derive(Debug, Clone, Copy)]
+pub struct PublicState<'a>(&'a GameState);
+
+impl<'a> PublicState<'a> {
+    pub const fn variant(self) -> &'static Variant {
+        self.0.variant
+    }
+    pub const fn num_players(self) -> usize {
+        self.0.num_players
+    }
+    pub fn hand(self, player: usize) -> &'a [CardOrder] {
+        &self.0.hands[player]
+    }
+    pub fn knowledge(self, order: CardOrder) -> &'a CardKnowledge {
+        &self.0.knowledge[order.0]
+    }
+    pub const fn clue_tokens(self) -> u8 {
+        self.0.clue_tokens
+    }
+    pub fn is_playable(self, card: Card) -> bool {
+        self.0.is_playable(card)
+    }
+    pub fn is_trash(self, card: Card) -> bool {
+        self.0.is_trash(card)
+    }
+    pub fn is_critical(self, card: Card) -> bool {
+        self.0.is_critical(card)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_variant() -> &'static Variant {
+        Variant::from_name("No Variant").unwrap()
+    }
+
+    // A deck dealt in order: the first five cards to seat 0, the next five
+    // to seat 1, then the draw pile
+    fn deck(cards: &[(usize, u8)]) -> Vec<Option<Card>> {
+        cards
+            .iter()
+            .map(|&(suit, rank)| Some(Card { suit, rank }))
+            .collect()
+    }
+
+    fn two_player_game(cards: &[(usize, u8)]) -> GameState {
+        GameState::new(no_variant(), 2, deck(cards)).unwrap()
+    }
+
+    const RED_THEN_YELLOW: &[(usize, u8)] = &[
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        (0, 5),
+        (1, 1),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (1, 5),
+        (2, 1),
+    ];
+
+    #[test]
+    fn shuffle_depends_only_on_seed() {
+        let variant = no_variant();
+        let deck = shuffled_deck(variant, 7);
+        assert_eq!(deck, shuffled_deck(variant, 7));
+        assert_ne!(deck, shuffled_deck(variant, 8));
+        assert_eq!(deck.len(), variant.deck_size());
+    }
+
+    #[test]
+    fn deals_newest_card_first() {
+        let state = two_player_game(RED_THEN_YELLOW);
+        let orders: Vec<_> = state.hand(0).iter().map(|x| x.0).collect();
+        assert_eq!(orders, [4, 3, 2, 1, 0]);
+        assert_eq!(state.hand(1).len(), 5);
+        assert_eq!(state.current_player(), 0);
+    }
+
+    #[test]
+    fn plays_score_and_game_ends_after_last_round() {
+        let mut state = two_player_game(RED_THEN_YELLOW);
+        // Draws the last card, so each player gets one more turn
+        state.apply(Action::Play(CardOrder(0))).unwrap();
+        assert_eq!(state.end(), None);
+        state.apply(Action::Play(CardOrder(5))).unwrap();
+        assert_eq!(state.end(), None);
+        state.apply(Action::Play(CardOrder(1))).unwrap();
+        assert_eq!(state.end(), Some(EndCondition::Normal));
+        assert_eq!(state.score(), 3);
+        assert_eq!(state.strikes(), 0);
+        assert!(state.apply(Action::Play(CardOrder(6))).is_err());
+    }
+
+    #[test]
+    fn misplays_strike_out() {
+        let mut state = two_player_game(RED_THEN_YELLOW);
+        state.apply(Action::Play(CardOrder(4))).unwrap();
+        assert_eq!(state.strikes(), 1);
+        state.apply(Action::Play(CardOrder(9))).unwrap();
+        assert_eq!(state.strikes(), 2);
+        assert_eq!(state.end(), None);
+        state.apply(Action::Play(CardOrder(3))).unwrap();
+        assert_eq!(state.end(), Some(EndCondition::Strikeout));
+        assert_eq!(state.score(), 0);
+    }
+
+    #[test]
+    fn clues_update_knowledge_and_tokens() {
+        let mut state = two_player_game(&[
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (0, 5),
+            (1, 1),
+            (1, 2),
+            (0, 1),
+            (1, 3),
+            (1, 4),
+        ]);
+        let red = Clue::Color(0);
+        state
+            .apply(Action::Clue {
+                target: 1,
+                clue: red,
+            })
+            .unwrap();
+        let public = PlayerView::new(&state, 1).public();
+        assert_eq!(public.clue_tokens(), MAX_CLUE_TOKENS - 1);
+        let touched = public.knowledge(CardOrder(7));
+        assert!(touched.clued);
+        assert!(touched.possible.iter().all(|card| card.suit == 0));
+        assert_eq!(touched.possible.len(), 5);
+        let untouched = public.knowledge(CardOrder(5));
+        assert!(!untouched.clued);
+        assert!(untouched.possible.iter().all(|card| card.suit != 0));
+        assert_eq!(state.current_player(), 1);
+    }
+
+    #[test]
+    fn rejects_illegal_clues() {
+        let mut state = two_player_game(RED_THEN_YELLOW);
+        // No red in seat 1's hand
+        let red = Clue::Color(0);
+        assert!(state
+            .apply(Action::Clue {
+                target: 1,
+                clue: red
+            })
+            .is_err());
+        let one = Clue::Rank(1);
+        assert!(state
+            .apply(Action::Clue {
+                target: 0,
+                clue: one
+            })
+            .is_err());
+        assert!(state
+            .apply(Action::Clue {
+                target: 2,
+                clue: one
+            })
+            .is_err());
+        // Nothing changed
+        assert_eq!(PlayerView::new(&state, 0).public().clue_tokens(), 8);
+        assert_eq!(state.turn(), 0);
+    }
+
+    #[test]
+    fn discards_need_a_spent_clue() {
+        let mut state = two_player_game(RED_THEN_YELLOW);
+        assert!(state.apply(Action::Discard(CardOrder(0))).is_err());
+        let one = Clue::Rank(1);
+        state
+            .apply(Action::Clue {
+                target: 1,
+                clue: one,
+            })
+            .unwrap();
+        state.apply(Action::Discard(CardOrder(9))).unwrap();
+        assert_eq!(PlayerView::new(&state, 0).public().clue_tokens(), 8);
+    }
+}
@@ -0,0 +1,153 @@
+use super::Card;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suit {
+    pub name: &'static str,
+    // Lowercase abbreviation, as used in hanab.live notes
+    pub abbreviation: char,
+    // Touched by every color clue, and has no color clue of its own
+    pub rainbow: bool,
+    // Only one copy of each rank
+    pub dark: bool,
+}
+
+impl Suit {
+    const fn normal(name: &'static str, abbreviation: char) -> Self {
+        Self {
+            name,
+            abbreviation,
+            rainbow: false,
+            dark: false,
+        }
+    }
+}
+
+const RED: Suit = Suit::normal("Red", 'r');
+const YELLOW: Suit = Suit::normal("Yellow", 'y');
+const GREEN: Suit = Suit::normal("Green", 'g');
+const BLUE: Suit = Suit::normal("Blue", 'b');
+const PURPLE: Suit = Suit::normal("Purple", 'p');
+const TEAL: Suit = Suit::normal("Teal", 't');
+const BLACK: Suit = Suit {
+    dark: true,
+    ..Suit::normal("Black", 'k')
+};
+const RAINBOW: Suit = Suit {
+    rainbow: true,
+    ..Suit::normal("Rainbow", 'm')
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Variant {
+    // Name as used by hanab.live
+    pub name: &'static str,
+    pub suits: &'static [Suit],
+}
+
+impl Variant {
+    pub const ALL: &'static [Self] = &[
+        Self {
+            name: "No Variant",
+            suits: &[RED, YELLOW, GREEN, BLUE, PURPLE],
+        },
+        Self {
+            name: "6 Suits",
+            suits: &[RED, YELLOW, GREEN, BLUE, PURPLE, TEAL],
+        },
+        Self {
+            name: "4 Suits",
+            suits: &[RED, YELLOW, GREEN, BLUE],
+        },
+        Self {
+            name: "3 Suits",
+            suits: &[RED, YELLOW, GREEN],
+        },
+        Self {
+            name: "Black (6 Suits)",
+            suits: &[RED, YELLOW, GREEN, BLUE, PURPLE, BLACK],
+        },
+        Self {
+            name: "Black (5 Suits)",
+            suits: &[RED, YELLOW, GREEN, BLUE, BLACK],
+        },
+        Self {
+            name: "Rainbow (6 Suits)",
+            suits: &[RED, YELLOW, GREEN, BLUE, PURPLE, RAINBOW],
+        },
+        Self {
+            name: "Rainbow (5 Suits)",
+            suits: &[RED, YELLOW, GREEN, BLUE, RAINBOW],
+        },
+    ];
+
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        Self::ALL.iter().find(|variant| variant.name == name)
+    }
+
+    // Suit indices that have a color clue, in hanab.live clue value order
+    pub fn clue_colors(&self) -> impl Iterator<Item = usize> + '_ {
+        self.suits
+            .iter()
+            .enumerate()
+            .filter(|(_, suit)| !suit.rainbow)
+            .map(|(i, _)| i)
+    }
+
+    pub fn max_score(&self) -> u32 {
+        5 * u32::try_from(self.suits.len()).unwrap()
+    }
+
+    pub fn deck_size(&self) -> usize {
+        (0..self.suits.len())
+            .flat_map(|suit| (1..=5).map(move |rank| self.copies(suit, rank)))
+            .map(usize::from)
+            .sum()
+    }
+
+    // Short name like "r1", as used in hanab.live notes
+    pub fn card_name(&self, card: Card) -> String {
+        format!("{}{}", self.suits[card.suit].abbreviation, card.rank)
+    }
+
+    // Number of copies of a card in the deck
+    pub fn copies(&self, suit: usize, rank: u8) -> u8 {
+        if self.suits[suit].dark {
+            return 1;
+        }
+        match rank {
+            1 => 3,
+            5 => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deck_sizes() {
+        let size = |name| Variant::from_name(name).unwrap().deck_size();
+        assert_eq!(size("No Variant"), 50);
+        assert_eq!(size("6 Suits"), 60);
+        assert_eq!(size("3 Suits"), 30);
+        // One copy of each black card
+        assert_eq!(size("Black (6 Suits)"), 55);
+    }
+
+    #[test]
+    fn rainbow_has_no_color_clue() {
+        let variant = Variant::from_name("Rainbow (5 Suits)").unwrap();
+        assert_eq!(variant.clue_colors().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(variant.max_score(), 25);
+    }
+
+    #[test]
+    fn card_names() {
+        let variant = Variant::from_name("Black (5 Suits)").unwrap();
+        assert_eq!(variant.card_name(Card { suit: 0, rank: 3 }), "r3");
+        assert_eq!(variant.card_name(Card { suit: 4, rank: 5 }), "k5");
+        assert!(Variant::from_name("Nonexistent").is_none());
+    }
+}
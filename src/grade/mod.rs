@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::{self, bail, eyre, WrapErr};
+use serde::Deserialize;
+
+use crate::game::{
+    Action, Card, CardOrder, Clue, GameState, PlayerView, Variant,
+};
+use crate::strategy::{describe_action, StrategyKind};
+
+// Replay a hanab.live game export and report where a strategy disagrees
+// with the moves that were actually made
+#[derive(Debug, clap::Args)]
+pub struct GradeArgs {
+    // JSON file, as exported from a hanab.live replay
+    file: PathBuf,
+    #[arg(short, long, value_enum, default_value_t = StrategyKind::Basic)]
+    strategy: StrategyKind,
+}
+
+// hanab.live's game export format
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GameExport {
+    players: Vec<String>,
+    deck: Vec<ExportCard>,
+    actions: Vec<ExportAction>,
+    #[serde(default)]
+    options: ExportOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportCard {
+    suit_index: usize,
+    rank: u8,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportAction {
+    // 0 play, 1 discard, 2 color clue, 3 rank clue, 4 game over
+    #[serde(rename = "type")]
+    kind: u8,
+    target: usize,
+    #[serde(default)]
+    value: u8,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportOptions {
+    variant: String,
+    // Seat that moved first
+    #[serde(default)]
+    starting_player: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            variant: "No Variant".to_owned(),
+            starting_player: 0,
+        }
+    }
+}
+
+// How a strategy's choices compared with the moves in an export
+#[derive(Debug)]
+struct Grade {
+    // One line per move the strategy wouldn't have made
+    disagreements: Vec<String>,
+    agreed: usize,
+    graded: usize,
+    score: u32,
+}
+
+impl ExportAction {
+    // None for the game over action
+    fn to_action(&self) -> eyre::Result<Option<Action>> {
+        Ok(Some(match self.kind {
+            0 => Action::Play(CardOrder(self.target)),
+            1 => Action::Discard(CardOrder(self.target)),
+            2 => Action::Clue {
+                target: self.target,
+                clue: Clue::Color(usize::from(self.value)),
+            },
+            3 => Action::Clue {
+                target: self.target,
+                clue: Clue::Rank(self.value),
+            },
+            4 => return Ok(None),
+            kind => bail!("unknown action type {kind}"),
+        }))
+    }
+}
+
+pub fn run(args: &GradeArgs) -> eyre::Result<()> {
+    let f = std::fs::read_to_string(&args.file)
+        .wrap_err_with(|| format!("reading {}", args.file.display()))?;
+    let export: GameExport = serde_json::from_str(&f)?;
+    let grade = grade(&export, args.strategy)?;
+    for line in &grade.disagreements {
+        println!("{line}");
+    }
+    println!(
+        "{:?} agreed with {} of {} moves; final score {}",
+        args.strategy, grade.agreed, grade.graded, grade.score
+    );
+    Ok(())
+}
+
+fn grade(export: &GameExport, strategy: StrategyKind) -> eyre::Result<Grade> {
+    let variant_name = &export.options.variant;
+    let variant = Variant::from_name(variant_name)
+        .ok_or_else(|| eyre!("unsupported variant {variant_name:?}"))?;
+    // The simulation always starts with the first seat
+    if export.options.starting_player != 0 {
+        bail!(
+            "unsupported starting player {}",
+            export.options.starting_player
+        );
+    }
+    let deck = export
+        .deck
+        .iter()
+        .enumerate()
+        .map(|(i, card)| {
+            if card.suit_index >= variant.suits.len() {
+                bail!(
+                    "deck card {i} has suit {}, but {} has {} suits",
+                    card.suit_index,
+                    variant.name,
+                    variant.suits.len()
+                );
+            }
+            if !(1..=5).contains(&card.rank) {
+                bail!("deck card {i} has rank {}", card.rank);
+            }
+            Ok(Some(Card {
+                suit: card.suit_index,
+                rank: card.rank,
+            }))
+        })
+        .collect::<eyre::Result<_>>()?;
+    let mut state = GameState::new(variant, export.players.len(), deck)?;
+    // One strategy per seat, so stateful strategies see a consistent game
+    let mut strategies: Vec<_> = (0..export.players.len())
+        .map(|seat| strategy.build(seat as u64))
+        .collect();
+
+    let mut grade = Grade {
+        disagreements: Vec::new(),
+        agreed: 0,
+        graded: 0,
+        score: 0,
+    };
+    for export_action in &export.actions {
+        let Some(actual) = export_action.to_action()? else {
+            break;
+        };
+        if state.end().is_some() {
+            break;
+        }
+        let seat = state.current_player();
+        let view = PlayerView::new(&state, seat);
+        let decision = strategies[seat].decide(view, None);
+        grade.graded += 1;
+        if decision.action == actual {
+            grade.agreed += 1;
+        } else {
+            grade.disagreements.push(format!(
+                "turn {} {}: chose to {}, {:?} would {}",
+                state.turn() + 1,
+                export.players[seat],
+                describe_action(view, actual),
+                strategy,
+                decision.describe(view),
+            ));
+        }
+        let turn = state.turn() + 1;
+        state
+            .apply(actual)
+            .wrap_err_with(|| format!("replaying turn {turn}"))?;
+    }
+    grade.score = state.score();
+    Ok(grade)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // Two players holding red 1-5 and yellow 1-5, with the given actions
+    fn export(actions: &serde_json::Value) -> serde_json::Value {
+        let deck: Vec<_> = (0..2)
+            .flat_map(|suit| {
+                (1..=5)
+                    .map(move |rank| json!({"suitIndex": suit, "rank": rank}))
+            })
+            .collect();
+        json!({
+            "players": ["alice", "bob"],
+            "deck": deck,
+            "actions": actions,
+        })
+    }
+
+    fn grade_json(export: serde_json::Value) -> eyre::Result<Grade> {
+        grade(&serde_json::from_value(export)?, StrategyKind::Basic)
+    }
+
+    #[test]
+    fn grades_valid_export() {
+        // alice plays red 1, then the game is ended
+        let grade = grade_json(export(&json!([
+            {"type": 0, "target": 0},
+            {"type": 4, "target": 0, "value": 1},
+        ])))
+        .unwrap();
+        assert_eq!(grade.graded, 1);
+        assert_eq!(grade.agreed + grade.disagreements.len(), 1);
+        assert_eq!(grade.score, 1);
+    }
+
+    #[test]
+    fn rejects_unknown_suit() {
+        let mut export = export(&json!([]));
+        export["deck"][3]["suitIndex"] = json!(9);
+        let e = grade_json(export).unwrap_err();
+        assert!(e.to_string().contains("deck card 3 has suit 9"), "{e}");
+    }
+
+    #[test]
+    fn rejects_rank_zero() {
+        let mut export = export(&json!([]));
+        export["deck"][0]["rank"] = json!(0);
+        let e = grade_json(export).unwrap_err();
+        assert!(e.to_string().contains("deck card 0 has rank 0"), "{e}");
+    }
+
+    #[test]
+    fn rejects_other_starting_player() {
+        let mut export = export(&json!([]));
+        export["options"] =
+            json!({"variant": "No Variant", "startingPlayer": 1});
+        let e = grade_json(export).unwrap_err();
+        assert!(e.to_string().contains("starting player 1"), "{e}");
+    }
+
+    #[test]
+    fn rejects_clue_to_missing_player() {
+        let export = export(&json!([{"type": 3, "target": 5, "value": 1}]));
+        assert!(grade_json(export).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use hanab_live::command::{TableID, UserID};
+
+// What a bot observed or did, for Bot::subscribe
+#[derive(Debug, Clone)]
+pub enum Event {
+    // --- Lobby
+    UserUpdated {
+        user_id: UserID,
+        name: String,
+        table_id: Option<TableID>,
+    },
+    UserLeft {
+        user_id: UserID,
+    },
+    TableUpdated {
+        table_id: TableID,
+        name: String,
+    },
+    TableGone {
+        table_id: TableID,
+    },
+    Joined {
+        table_id: TableID,
+    },
+    Left {
+        table_id: TableID,
+    },
+    // recipient is None for public chat
+    ChatReceived {
+        who: String,
+        msg: String,
+        recipient: Option<String>,
+    },
+    // --- Games we're playing
+    GameStarted {
+        table_id: TableID,
+        player_names: Vec<String>,
+        our_index: usize,
+    },
+    // Our own moves. Turns count from 1 like on hanab.live.
+    ActionTaken {
+        table_id: TableID,
+        turn: usize,
+        explanation: String,
+    },
+    GameOver {
+        table_id: TableID,
+        score: u32,
+    },
+    // Always the last event. error is None for a clean close.
+    Disconnected {
+        error: Option<String>,
+    },
+}
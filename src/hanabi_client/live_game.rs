@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{self, bail, eyre};
+use hanab_live::command::{client, server, TableID};
+
+use crate::game::{
+    Action, Card, CardOrder, Clue, GameState, PlayerView, Variant,
+};
+use crate::strategy::Strategy;
+
+// A game on hanab.live that the bot is seated in
+#[derive(Debug)]
+pub struct LiveGame {
+    pub table_id: TableID,
+    our_index: usize,
+    state: GameState,
+    strategy: Box<dyn Strategy>,
+    // Notes we have on cards, by order. Used so we only send changes.
+    notes: HashMap<CardOrder, String>,
+    // Explanations of the actions we sent, by turn. Turns count from 1 like
+    // on hanab.live.
+    decisions: BTreeMap<usize, String>,
+    // The turn the server last said is ours, and the last turn we sent an
+    // action for, both counting from 0 like GameState::turn
+    turn_started: Option<usize>,
+    sent_turn: Option<usize>,
+    finished: bool,
+    // Timed games only
+    time_control: Option<TimeControl>,
+    clock: Option<ClockReading>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TimeControl {
+    base: Duration,
+    per_turn: Duration,
+}
+
+// Our time left, as of the last clock update from the server
+#[derive(Debug, Clone, Copy)]
+struct ClockReading {
+    left_ms: i64,
+    received: Instant,
+    // Whether our clock was running at the time
+    running: bool,
+}
+
+impl ClockReading {
+    fn left(&self, now: Instant) -> Duration {
+        let elapsed = if self.running {
+            now.saturating_duration_since(self.received)
+        } else {
+            Duration::ZERO
+        };
+        Duration::from_millis(u64::try_from(self.left_ms).unwrap_or(0))
+            .saturating_sub(elapsed)
+    }
+}
+
+// An action decided on, recorded with LiveGame::sent once it's sent
+#[derive(Debug)]
+pub struct Move {
+    // Counting from 1
+    pub turn: usize,
+    pub action: client::Action,
+    pub explanation: String,
+}
+
+// Time kept in reserve for network latency
+const CLOCK_SAFETY_MARGIN: Duration = Duration::from_secs(2);
+
+impl LiveGame {
+    pub fn new(
+        init: &server::Init,
+        strategy: Box<dyn Strategy>,
+    ) -> eyre::Result<Self> {
+        let variant_name = &init.options.variant_name;
+        let variant = Variant::from_name(variant_name)
+            .ok_or_else(|| eyre!("unsupported variant {variant_name:?}"))?;
+        let our_index = usize::try_from(init.our_player_index)
+            .map_err(|_| eyre!("not a player at this table"))?;
+        let deck = vec![None; variant.deck_size()];
+        let state = GameState::new(variant, init.options.num_players, deck)?;
+        Ok(Self {
+            table_id: init.table_id,
+            our_index,
+            state,
+            strategy,
+            notes: HashMap::new(),
+            decisions: BTreeMap::new(),
+            turn_started: None,
+            sent_turn: None,
+            finished: false,
+            time_control: init.options.timed.then(|| TimeControl {
+                base: Duration::from_secs(init.options.time_base),
+                per_turn: Duration::from_secs(init.options.time_per_turn),
+            }),
+            clock: None,
+        })
+    }
+
+    pub const fn our_index(&self) -> usize {
+        self.our_index
+    }
+
+    pub fn score(&self) -> u32 {
+        self.state.score()
+    }
+
+    pub const fn strikes(&self) -> u8 {
+        self.state.strikes()
+    }
+
+    // Whether to act now. Our model moves on to the next player as soon as
+    // an action is applied, but the server's draw for it comes after, so we
+    // wait for its turn action, then act once.
+    pub fn is_our_turn(&self) -> bool {
+        let turn = self.state.turn();
+        !self.finished
+            && self.state.end().is_none()
+            && self.state.current_player() == self.our_index
+            && self.turn_started == Some(turn)
+            && self.sent_turn != Some(turn)
+    }
+
+    pub fn apply(
+        &mut self,
+        action: server::GameActionType,
+    ) -> eyre::Result<()> {
+        use server::GameActionType as A;
+        match action {
+            A::Draw {
+                player_index,
+                order,
+                suit_index,
+                rank,
+            } => {
+                // Our model draws as soon as a card leaves a hand, so this
+                // only reveals the identity
+                let order = CardOrder(order);
+                if !self.state.hand(player_index).contains(&order) {
+                    bail!("out of sync: player {player_index} drew {order:?}");
+                }
+                self.reveal(order, suit_index, rank);
+            }
+            A::Play {
+                order,
+                suit_index,
+                rank,
+                ..
+            }
+            | A::Discard {
+                order,
+                suit_index,
+                rank,
+                failed: true,
+                ..
+            } => {
+                self.reveal(CardOrder(order), suit_index, rank);
+                self.state.apply(Action::Play(CardOrder(order)))?;
+            }
+            A::Discard {
+                order,
+                suit_index,
+                rank,
+                ..
+            } => {
+                self.reveal(CardOrder(order), suit_index, rank);
+                self.state.apply(Action::Discard(CardOrder(order)))?;
+            }
+            A::Clue {
+                target, list, clue, ..
+            } => {
+                let clue = match clue.kind {
+                    0 => Clue::Color(usize::from(clue.value)),
+                    _ => Clue::Rank(clue.value),
+                };
+                let touched: Vec<_> = list.into_iter().map(CardOrder).collect();
+                self.state.apply_clue(target, clue, &touched)?;
+            }
+            A::Turn {
+                current_player_index,
+                ..
+            } => {
+                let expected = self.state.current_player();
+                if self.state.end().is_none()
+                    && usize::try_from(current_player_index) != Ok(expected)
+                {
+                    bail!(
+                        "out of sync: server says it's player \
+                         {current_player_index}'s turn, expected {expected}"
+                    );
+                }
+                if usize::try_from(current_player_index) == Ok(self.our_index) {
+                    self.turn_started = Some(self.state.turn());
+                }
+            }
+            A::GameOver => self.finished = true,
+            A::Other => {}
+        }
+        Ok(())
+    }
+
+    fn reveal(&mut self, order: CardOrder, suit_index: i32, rank: i32) {
+        if let (Ok(suit), Ok(rank)) =
+            (usize::try_from(suit_index), u8::try_from(rank))
+        {
+            self.state.reveal(order, Card { suit, rank });
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: &server::Clock) {
+        let Some(&left_ms) = clock.times.get(self.our_index) else {
+            return;
+        };
+        self.clock = Some(ClockReading {
+            left_ms,
+            received: Instant::now(),
+            running: usize::try_from(clock.active_player_index)
+                == Ok(self.our_index),
+        });
+    }
+
+    // When we must have decided by, in timed games. We spend the per-turn
+    // increment plus a slice of the time bank.
+    fn deadline(&self) -> Option<Instant> {
+        let time_control = self.time_control?;
+        let now = Instant::now();
+        let left = self
+            .clock
+            .map_or(time_control.base, |clock| clock.left(now));
+        let budget = (time_control.per_turn + left / 8)
+            .min(left)
+            .saturating_sub(CLOCK_SAFETY_MARGIN);
+        Some(now + budget)
+    }
+
+    #[tracing::instrument(skip_all, fields(turn = self.state.turn() + 1))]
+    pub fn decide(&mut self) -> Move {
+        let view = PlayerView::new(&self.state, self.our_index);
+        let deadline = self.deadline();
+        let decision = self.strategy.decide(view, deadline);
+        if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            tracing::warn!("strategy overran its time budget");
+        }
+        let explanation = decision.describe(view);
+        tracing::info!(?decision, "decided to {explanation}");
+        let (kind, target, value) = match decision.action {
+            Action::Play(order) => (client::ActionType::Play, order.0, None),
+            Action::Discard(order) => {
+                (client::ActionType::Discard, order.0, None)
+            }
+            Action::Clue {
+                target,
+                clue: Clue::Color(color),
+            } => (
+                client::ActionType::ColorClue,
+                target,
+                Some(u8::try_from(color).unwrap()),
+            ),
+            Action::Clue {
+                target,
+                clue: Clue::Rank(rank),
+            } => (client::ActionType::RankClue, target, Some(rank)),
+        };
+        Move {
+            turn: self.state.turn() + 1,
+            action: client::Action {
+                table_id: self.table_id,
+                kind,
+                target,
+                value,
+            },
+            explanation,
+        }
+    }
+
+    pub fn sent(&mut self, sent: Move) {
+        self.sent_turn = Some(sent.turn - 1);
+        self.decisions.insert(sent.turn, sent.explanation);
+    }
+
+    pub fn explanation(&self, turn: usize) -> Option<&str> {
+        self.decisions.get(&turn).map(String::as_str)
+    }
+
+    pub const fn explanations(&self) -> &BTreeMap<usize, String> {
+        &self.decisions
+    }
+
+    pub const fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Notes already on the cards, e.g. from before a reconnect
+    pub fn set_notes(&mut self, notes: Vec<String>) {
+        self.notes = notes
+            .into_iter()
+            .enumerate()
+            .filter(|(_, note)| !note.is_empty())
+            .map(|(order, note)| (CardOrder(order), note))
+            .collect();
+    }
+
+    // Notes on our cards that need to change to match what we now believe
+    pub fn note_updates(&mut self) -> Vec<client::Note> {
+        let view = PlayerView::new(&self.state, self.our_index);
+        let mut updates = Vec::new();
+        for &order in view.public().hand(self.our_index) {
+            let Some(note) = note_text(view, order) else {
+                continue;
+            };
+            if self.notes.get(&order) != Some(&note) {
+                self.notes.insert(order, note.clone());
+                updates.push(client::Note {
+                    table_id: self.table_id,
+                    order: order.0,
+                    note,
+                });
+            }
+        }
+        updates
+    }
+}
+
+// hanab.live note syntax: "r1,r2" for the possible identities, "kt" for
+// known trash. None if we know nothing worth writing down.
+fn note_text(view: PlayerView, order: CardOrder) -> Option<String> {
+    let public = view.public();
+    let variant = public.variant();
+    let possible = view.possible(order);
+    if possible.is_empty() {
+        return None;
+    }
+    if possible.iter().all(|card| public.is_trash(card)) {
+        return Some("kt".to_owned());
+    }
+    // Negative information alone is rarely worth a note
+    if !public.knowledge(order).clued && possible.len() > 3 {
+        return None;
+    }
+    let cards: Vec<String> = possible
+        .iter()
+        .map(|card| variant.card_name(card))
+        .collect();
+    Some(cards.join(","))
+}
@@ -0,0 +1,329 @@
+use std::time::{Duration, Instant};
+
+use hanab_live::command::TableID;
+use tokio::sync::oneshot;
+
+// How long the server gets to answer a lobby request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// What the bot is doing in the hanab.live lobby. Requests that wait on the
+// server carry a Pending, so they can time out and their outcome can be
+// reported to whoever asked.
+#[derive(Debug, Default)]
+pub enum Lobby {
+    #[default]
+    Idle,
+    // Sent tableCreate, waiting to be joined to the new table
+    Creating {
+        request: Pending,
+    },
+    // Waiting for a table with this name to show up, then to be joined to it
+    Joining {
+        table_name: String,
+        // Set once we sent tableJoin
+        table_id: Option<TableID>,
+        request: Pending,
+    },
+    // Constantly try to go to this user's table
+    Following {
+        username: String,
+        // Set while a tableJoin is in flight
+        joining: Option<(TableID, Pending)>,
+        requester: Requester,
+        // Answered by the first join attempt
+        reply: Option<Reply>,
+        // A table of theirs we couldn't join. It isn't tried again until
+        // they move to another one.
+        refused: Option<TableID>,
+    },
+    // At a table, waiting for it to start. following is who we go back to
+    // following if we leave.
+    Seated {
+        table_id: TableID,
+        following: Option<Following>,
+        // Set while a tableStart is in flight
+        starting: Option<Pending>,
+    },
+    InGame {
+        table_id: TableID,
+        following: Option<Following>,
+    },
+    Spectating {
+        table_id: TableID,
+        following: Option<Following>,
+    },
+}
+
+// Who asked for a lobby action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requester {
+    // The program driving the bot, through Bot
+    Local,
+    // A hanab.live user, through a chat command. Failures are sent as a PM.
+    User(String),
+}
+
+// Where the outcome of a request goes: the table we ended up at
+pub type Reply = oneshot::Sender<Result<TableID, LobbyError>>;
+
+#[derive(Debug)]
+pub struct Pending {
+    pub requester: Requester,
+    pub reply: Option<Reply>,
+    pub deadline: Instant,
+}
+
+impl Pending {
+    pub fn new(requester: Requester, reply: Option<Reply>) -> Self {
+        Self {
+            requester,
+            reply,
+            deadline: Instant::now() + REQUEST_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Following {
+    pub username: String,
+    pub requester: Requester,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LobbyError {
+    #[error("already at table {0}")]
+    AtTable(TableID),
+    #[error("another lobby request is in progress")]
+    Busy,
+    #[error("not at a table")]
+    NotAtTable,
+    #[error("can't leave in the middle of a game")]
+    Playing,
+    #[error("{0} isn't at a table")]
+    UserNotAtTable(String),
+    #[error("{0}'s table can't be joined")]
+    NotJoinable(String),
+    #[error("server error: {0}")]
+    Server(String),
+    #[error("server warning: {0}")]
+    Warning(String),
+    #[error("timed out waiting for the server")]
+    Timeout,
+    #[error("the bot is no longer running")]
+    Stopped,
+}
+
+impl Lobby {
+    // The table we're at, if any
+    pub const fn table_id(&self) -> Option<TableID> {
+        match self {
+            Self::Seated { table_id, .. }
+            | Self::InGame { table_id, .. }
+            | Self::Spectating { table_id, .. } => Some(*table_id),
+            _ => None,
+        }
+    }
+
+    // Short name for this state, for status reports
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Creating { .. } => "creating",
+            Self::Joining { .. } => "joining",
+            Self::Following { .. } => "following",
+            Self::Seated { .. } => "seated",
+            Self::InGame { .. } => "playing",
+            Self::Spectating { .. } => "spectating",
+        }
+    }
+
+    // Who we go to the table of, if anyone
+    pub fn following(&self) -> Option<&str> {
+        match self {
+            Self::Following { username, .. } => Some(username),
+            Self::Seated { following, .. }
+            | Self::InGame { following, .. }
+            | Self::Spectating { following, .. } => {
+                following.as_ref().map(|x| x.username.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    // Whether a new request can replace this state. Following is only a
+    // standing intent, so it can be replaced unless a join is in flight.
+    pub const fn check_available(&self) -> Result<(), LobbyError> {
+        match self {
+            Self::Idle | Self::Following { joining: None, .. } => Ok(()),
+            Self::Creating { .. }
+            | Self::Joining { .. }
+            | Self::Following { .. } => Err(LobbyError::Busy),
+            Self::Seated { table_id, .. }
+            | Self::InGame { table_id, .. }
+            | Self::Spectating { table_id, .. } => {
+                Err(LobbyError::AtTable(*table_id))
+            }
+        }
+    }
+
+    // The request waiting on the server, if any
+    pub const fn pending(&self) -> Option<&Pending> {
+        match self {
+            Self::Creating { request } | Self::Joining { request, .. } => {
+                Some(request)
+            }
+            Self::Following {
+                joining: Some((_, request)),
+                ..
+            }
+            | Self::Seated {
+                starting: Some(request),
+                ..
+            } => Some(request),
+            _ => None,
+        }
+    }
+
+    // Whether the command the pending request waits on has been sent, so
+    // that a warning from the server is the answer to it
+    pub const fn awaiting_answer(&self) -> bool {
+        matches!(
+            self,
+            Self::Creating { .. }
+                | Self::Joining {
+                    table_id: Some(_),
+                    ..
+                }
+                | Self::Following {
+                    joining: Some(_),
+                    ..
+                }
+                | Self::Seated {
+                    starting: Some(_),
+                    ..
+                }
+        )
+    }
+
+    // Abandon the pending request, returning it so its failure can be
+    // reported. Following keeps following, and tries again once the user
+    // moves to another table.
+    pub fn fail_pending(&mut self) -> Option<Pending> {
+        match self {
+            Self::Creating { .. } | Self::Joining { .. } => {
+                match std::mem::take(self) {
+                    Self::Creating { request }
+                    | Self::Joining { request, .. } => Some(request),
+                    _ => unreachable!(),
+                }
+            }
+            Self::Following {
+                joining, refused, ..
+            } => joining.take().map(|(table_id, request)| {
+                *refused = Some(table_id);
+                request
+            }),
+            Self::Seated { starting, .. } => starting.take(),
+            _ => None,
+        }
+    }
+
+    // We've been joined to a table, whether we asked for it or not. Returns
+    // the request this completes.
+    pub fn joined(&mut self, table_id: TableID) -> Option<Pending> {
+        let (request, following) = match std::mem::take(self) {
+            Self::Creating { request } | Self::Joining { request, .. } => {
+                (Some(request), None)
+            }
+            Self::Following {
+                username,
+                joining,
+                requester,
+                reply,
+                ..
+            } => (
+                // Without a join in flight, the server put us at a table by
+                // itself, e.g. after a reconnect
+                joining.map(|(_, request)| request).or_else(|| {
+                    reply.map(|reply| {
+                        Pending::new(requester.clone(), Some(reply))
+                    })
+                }),
+                Some(Following {
+                    username,
+                    requester,
+                }),
+            ),
+            other => {
+                tracing::warn!(
+                    "joined table {table_id} unexpectedly while {other:?}"
+                );
+                (None, None)
+            }
+        };
+        *self = Self::Seated {
+            table_id,
+            following,
+            starting: None,
+        };
+        request
+    }
+
+    // We've left our table, or it went away
+    pub fn left(&mut self) {
+        let following = match self {
+            Self::Seated { following, .. }
+            | Self::InGame { following, .. }
+            | Self::Spectating { following, .. } => following.take(),
+            _ => return,
+        };
+        *self = following.map_or(
+            Self::Idle,
+            |Following {
+                 username,
+                 requester,
+             }| Self::Following {
+                username,
+                joining: None,
+                requester,
+                reply: None,
+                refused: None,
+            },
+        );
+    }
+
+    // Returns the start request this completes
+    pub fn game_started(
+        &mut self,
+        table_id: TableID,
+        spectating: bool,
+    ) -> Option<Pending> {
+        let (following, starting) = match std::mem::take(self) {
+            Self::Seated {
+                following,
+                starting,
+                ..
+            } => (following, starting),
+            Self::InGame { following, .. }
+            | Self::Spectating { following, .. } => (following, None),
+            other => {
+                tracing::warn!(
+                    "game started at table {table_id} while {other:?}"
+                );
+                (None, None)
+            }
+        };
+        *self = if spectating {
+            Self::Spectating {
+                table_id,
+                following,
+            }
+        } else {
+            Self::InGame {
+                table_id,
+                following,
+            }
+        };
+        starting
+    }
+}
@@ -1,346 +1,1135 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::ops::ControlFlow::{self, Break, Continue};
-use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use async_trait::async_trait;
 use clap::Parser;
-use color_eyre::eyre::{self, eyre, WrapErr};
+use color_eyre::eyre::{self, bail};
 use futures::prelude::*;
-use reqwest::cookie::CookieStore;
+use hanab_live::command::server::{ServerMessage, UserStatus};
+use hanab_live::command::{client, server, Command, TableID, UserID};
 use serde::Serialize;
-use serde_json::json;
-use tracing::instrument;
-use url::Url;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{instrument, Instrument};
 
-use crate::chat_command::ChatCommand;
-use crate::command::{self, client, server, Command, TableID, UserID};
+use crate::chat_command::{Access, ChatCommand};
+use crate::config::BotConfig;
+use crate::metrics::BotMetrics;
+use crate::strategy::StrategyKind;
+
+mod event;
+mod live_game;
+mod lobby;
+mod recording;
+pub mod replay;
+mod session;
+#[cfg(test)]
+mod tests;
+mod transport;
+
+use live_game::LiveGame;
+use lobby::{Lobby, Pending, Reply, Requester};
+use recording::{Frame, RecordedCall};
+use transport::{Connection, Incoming, Transport};
+
+pub use event::Event;
+pub use lobby::LobbyError;
+pub use recording::Recording;
+pub use session::{write_private, SessionCache};
+pub use transport::TransportKind;
 
 #[derive(Debug)]
 struct State {
-    // --- Wrapped ezsockets::Client handle
-    handle: Bot,
+    // --- Connection to hanab.live
+    handle: Handle,
     // --- State for hanab.live lobby
     users: HashMap<UserID, server::User>,
     tables: HashMap<TableID, server::Table>,
-    // Current table
-    // TODO if we are currently creating a table, this might be set too eagerly:
-    // creating the table might fail.
-    current_table: Option<TableID>,
     // --- Bot-specific state for lobby
-    // Constantly try to go to this user's table.
-    follow_user: RefCell<Option<String>>,
-    // Table that bot wants to join. Only join the table once.
-    join_table: RefCell<Option<String>>,
+    lobby: Lobby,
+    // Post decision explanations to table chat after each game
+    explain: bool,
+    // Go back to the lobby after each game, ready for the next invitation
+    daemon: bool,
+    // Who asked us, through chat, to the table we're at or last went to
+    invited_by: Option<String>,
+    // Who may give us chat commands
+    access: Access,
+    // --- State for the game we're playing in, or last played
+    // Plays each new game
+    strategy: StrategyKind,
+    game: Option<LiveGame>,
+}
+
+// Our end of the connection, and where events go out to subscribers. Kept
+// apart from the rest of State so it can be used while other fields are
+// borrowed.
+#[derive(Debug)]
+struct Handle {
+    // Informational
+    username: String,
+    transport: Box<dyn Transport>,
+    events: broadcast::Sender<Event>,
+    recording: Option<Recording>,
+    metrics: BotMetrics,
+}
+
+impl Handle {
+    fn send_command<T>(&self, command: &T)
+    where
+        T: Command + Serialize,
+    {
+        let text = command.serialize_command();
+        self.metrics.command_sent(T::NAME);
+        self.record(|| Frame::Out(recording::redact(&text)));
+        self.transport.send(text);
+    }
+    fn record(&self, frame: impl FnOnce() -> Frame) {
+        if let Some(recording) = &self.recording {
+            recording.record(&self.username, frame());
+        }
+    }
+    fn emit(&self, event: Event) {
+        // Fails only when nobody is subscribed
+        let _ = self.events.send(event);
+    }
 }
 
 impl State {
-    fn new(username: String, handle: ezsockets::Client<Self>) -> Self {
+    fn new(
+        username: String,
+        transport: Box<dyn Transport>,
+        events: broadcast::Sender<Event>,
+        recording: Option<Recording>,
+        strategy: StrategyKind,
+        access: Access,
+    ) -> Self {
         Self {
-            handle: Bot::from_handle(username, handle),
+            handle: Handle {
+                metrics: BotMetrics::new(&username),
+                username,
+                transport,
+                events,
+                recording,
+            },
             users: HashMap::new(),
             tables: HashMap::new(),
-            current_table: None,
-            follow_user: RefCell::new(None),
-            join_table: RefCell::new(None),
+            lobby: Lobby::Idle,
+            explain: false,
+            daemon: false,
+            invited_by: None,
+            access,
+            strategy,
+            game: None,
         }
     }
     fn username(&self) -> &str {
         &self.handle.username
     }
+    // For log spans
+    fn table_id(&self) -> Option<u64> {
+        self.lobby.table_id().map(u64::from)
+    }
     fn insert_user(&mut self, user: server::User) {
-        self.check_follow_user(&user);
+        let name = user.name.clone();
+        self.handle.emit(Event::UserUpdated {
+            user_id: user.user_id,
+            name: name.clone(),
+            table_id: user.table_id,
+        });
         self.users.insert(user.user_id, user);
+        self.check_follow_user(&name);
     }
     fn remove_user(&mut self, user_id: UserID) {
         let removed = self.users.remove(&user_id);
         if removed.is_none() {
             tracing::error!("called remove_user, but {user_id:?} not found");
         }
+        self.handle.emit(Event::UserLeft { user_id });
     }
     fn insert_table(&mut self, table: server::Table) {
-        self.check_join_table(&table);
+        self.check_join_table(&table.name, table.id);
+        self.handle.emit(Event::TableUpdated {
+            table_id: table.id,
+            name: table.name.clone(),
+        });
         self.tables.insert(table.id, table);
     }
     fn remove_table(&mut self, table_id: TableID) {
         self.tables.remove(&table_id);
+        if self.lobby.table_id() == Some(table_id) {
+            self.left();
+        }
+        self.handle.emit(Event::TableGone { table_id });
     }
-    // TODO maybe there should be an expected current_table
-    fn set_current_table(&mut self, current_table: Option<TableID>) {
-        self.current_table = current_table;
+
+    fn user_by_name(&self, name: &str) -> Option<&server::User> {
+        self.users.values().find(|user| user.name == name)
+    }
+    // Users seated at or spectating a table
+    fn users_at_table(
+        &self,
+        table_id: TableID,
+    ) -> impl Iterator<Item = &server::User> {
+        self.users
+            .values()
+            .filter(move |user| user.table_id == Some(table_id))
+    }
+    // Whether a user is online and in the lobby, not at any table
+    fn is_idle_in_lobby(&self, name: &str) -> bool {
+        self.user_by_name(name).is_some_and(|user| {
+            user.status == UserStatus::Lobby && user.table_id.is_none()
+        })
     }
 
-    fn create_table(&mut self, table: client::TableCreate) {
-        if self.current_table.is_some() {
-            return;
+    fn create_table(&mut self, table: &client::TableCreate, request: Pending) {
+        if let Err(e) = self.lobby.check_available() {
+            return self.resolve(request, Err(e));
         }
-        self.handle.send_command(&table);
+        self.handle.send_command(table);
+        self.lobby = Lobby::Creating { request };
     }
-    fn join_table(&mut self, table_name: String) {
-        *self.join_table.borrow_mut() = Some(table_name);
-        self.tables
+    fn join_table(&mut self, table_name: String, request: Pending) {
+        if let Err(e) = self.lobby.check_available() {
+            return self.resolve(request, Err(e));
+        }
+        self.lobby = Lobby::Joining {
+            table_name,
+            table_id: None,
+            request,
+        };
+        let tables: Vec<_> = self
+            .tables
             .values()
-            .try_for_each(|table| self.check_join_table(table));
+            .map(|table| (table.name.clone(), table.id))
+            .collect();
+        for (name, table_id) in &tables {
+            self.check_join_table(name, *table_id);
+        }
     }
-    fn check_join_table(&self, table: &server::Table) -> ControlFlow<()> {
-        let mut join_table = self.join_table.borrow_mut();
-        if join_table.as_ref().is_some_and(|x| x == &table.name) {
-            self.handle
-                .send_command(&client::TableJoin { table_id: table.id });
-            *join_table = None;
-            return Break(());
+    fn check_join_table(&mut self, name: &str, id: TableID) {
+        if let Lobby::Joining {
+            table_name,
+            table_id: table_id @ None,
+            ..
+        } = &mut self.lobby
+        {
+            if table_name == name {
+                self.handle.send_command(&client::TableJoin {
+                    table_id: id,
+                    password: None,
+                });
+                *table_id = Some(id);
+            }
         }
-        Continue(())
     }
-    fn follow_user(&mut self, username: String) {
-        *self.follow_user.borrow_mut() = Some(username);
-        self.users
-            .values()
-            .try_for_each(|user| self.check_follow_user(user));
+    fn follow_user(&mut self, username: String, request: Pending) {
+        if let Err(e) = self.lobby.check_available() {
+            return self.resolve(request, Err(e));
+        }
+        self.lobby = Lobby::Following {
+            username,
+            joining: None,
+            requester: request.requester,
+            reply: request.reply,
+            refused: None,
+        };
+        self.check_following();
+    }
+    fn check_following(&mut self) {
+        if let Lobby::Following { username, .. } = &self.lobby {
+            let username = username.clone();
+            self.check_follow_user(&username);
+        }
+    }
+    // Act on where the user we follow is now: join their table once they sit
+    // at one, and leave ours once they've gone elsewhere
+    fn check_follow_user(&mut self, name: &str) {
+        // A table that has started can't be joined
+        let their_table = self
+            .user_by_name(name)
+            .filter(|user| user.status == UserStatus::PreGame)
+            .and_then(|user| user.table_id);
+        match &mut self.lobby {
+            Lobby::Following {
+                username,
+                joining: joining @ None,
+                requester,
+                reply,
+                refused,
+            } if username == name => {
+                if their_table == *refused {
+                    return;
+                }
+                *refused = None;
+                if let Some(table_id) = their_table {
+                    self.handle.send_command(&client::TableJoin {
+                        table_id,
+                        password: None,
+                    });
+                    let request = Pending::new(requester.clone(), reply.take());
+                    *joining = Some((table_id, request));
+                }
+            }
+            &mut Lobby::Seated {
+                table_id,
+                following: Some(ref following),
+                starting: None,
+            } if following.username == name => {
+                let gone = self.user_by_name(name).is_some()
+                    && !self.users_at_table(table_id).any(|x| x.name == name);
+                if gone {
+                    self.handle.send_command(&client::TableLeave { table_id });
+                }
+            }
+            _ => {}
+        }
+    }
+    // Join the table a user is seated at, when they invite us with /join
+    fn join_user_table(
+        &mut self,
+        name: &str,
+        password: Option<String>,
+        request: Pending,
+    ) {
+        if let Err(e) = self.lobby.check_available() {
+            return self.resolve(request, Err(e));
+        }
+        let table_id = self
+            .user_by_name(name)
+            .filter(|user| user.status == UserStatus::PreGame)
+            .and_then(|user| user.table_id);
+        let Some(table_id) = table_id else {
+            let e = if self.is_idle_in_lobby(name) {
+                LobbyError::UserNotAtTable(name.to_owned())
+            } else {
+                LobbyError::NotJoinable(name.to_owned())
+            };
+            return self.resolve(request, Err(e));
+        };
+        self.handle
+            .send_command(&client::TableJoin { table_id, password });
+        self.lobby = Lobby::Joining {
+            table_name: self
+                .tables
+                .get(&table_id)
+                .map(|table| table.name.clone())
+                .unwrap_or_default(),
+            table_id: Some(table_id),
+            request,
+        };
+    }
+    // Leave our table, and stop following anyone. Done as soon as it's sent.
+    fn leave(&mut self, request: Pending) {
+        let playing = self.game.as_ref().is_some_and(|game| {
+            Some(game.table_id) == self.lobby.table_id() && !game.is_finished()
+        });
+        match self.lobby {
+            Lobby::Seated { table_id, .. } => {
+                self.handle.send_command(&client::TableLeave { table_id });
+                self.lobby = Lobby::Idle;
+                self.resolve(request, Ok(table_id));
+            }
+            Lobby::InGame { .. } if playing => {
+                self.resolve(request, Err(LobbyError::Playing));
+            }
+            Lobby::InGame { table_id, .. }
+            | Lobby::Spectating { table_id, .. } => {
+                self.handle
+                    .send_command(&client::TableUnattend { table_id });
+                self.lobby = Lobby::Idle;
+                self.handle.emit(Event::Left { table_id });
+                self.resolve(request, Ok(table_id));
+            }
+            _ => self.resolve(request, Err(LobbyError::NotAtTable)),
+        }
+    }
+    fn start(&mut self, request: Pending) {
+        match &mut self.lobby {
+            Lobby::Seated {
+                table_id,
+                starting: starting @ None,
+                ..
+            } => {
+                self.handle.send_command(&client::TableStart {
+                    table_id: *table_id,
+                });
+                *starting = Some(request);
+            }
+            Lobby::Seated { .. } => {
+                self.resolve(request, Err(LobbyError::Busy));
+            }
+            _ => self.resolve(request, Err(LobbyError::NotAtTable)),
+        }
+    }
+
+    // We've left our table, or it went away. If we were following someone,
+    // go after them.
+    fn left(&mut self) {
+        self.lobby.left();
+        self.check_following();
+    }
+    fn joined(&mut self, table_id: TableID) {
+        let request = self.lobby.joined(table_id);
+        self.invited_by = match request.as_ref().map(|x| &x.requester) {
+            Some(Requester::User(name)) => Some(name.clone()),
+            _ => None,
+        };
+        if let Some(request) = request {
+            self.resolve(request, Ok(table_id));
+        }
+        self.handle.emit(Event::Joined { table_id });
     }
-    fn check_follow_user(&self, user: &server::User) -> ControlFlow<()> {
-        let follow_user = self.follow_user.borrow_mut();
-        if follow_user.as_ref().is_some_and(|x| x == &user.name) {
-            if let Some(table_id) = user.table_id {
-                self.handle.send_command(&client::TableJoin { table_id });
-                // Don't stop following follow_user
-                return Break(());
+    // Server errors and warnings fail the pending lobby request, if there is
+    // one. Returns whether there was.
+    fn fail_pending(&mut self, error: LobbyError) -> bool {
+        let Some(request) = self.lobby.fail_pending() else {
+            return false;
+        };
+        self.resolve(request, Err(error));
+        true
+    }
+    fn check_timeouts(&mut self) {
+        let now = Instant::now();
+        if self.lobby.pending().is_some_and(|x| x.deadline <= now) {
+            self.handle.record(|| Frame::Expired);
+            self.fail_pending(LobbyError::Timeout);
+        }
+    }
+    // Report a request's outcome to whoever made it
+    fn resolve(&self, request: Pending, result: Result<TableID, LobbyError>) {
+        if let (Requester::User(name), Err(e)) = (&request.requester, &result) {
+            self.send_pm(name.clone(), format!("Couldn't do that: {e}"));
+        }
+        let unheard = match request.reply {
+            Some(reply) => reply.send(result).err(),
+            None => Some(result),
+        };
+        if let (Requester::Local, Some(Err(e))) = (&request.requester, unheard)
+        {
+            tracing::error!("lobby request failed: {e}");
+        }
+    }
+}
+
+impl State {
+    fn init_game(&mut self, init: &server::Init) {
+        if init.replay {
+            return;
+        }
+        if let Some(request) =
+            self.lobby.game_started(init.table_id, init.spectating)
+        {
+            self.resolve(request, Ok(init.table_id));
+        }
+        if init.spectating {
+            return;
+        }
+        match LiveGame::new(init, self.strategy.build(0)) {
+            Ok(game) => {
+                self.handle.emit(Event::GameStarted {
+                    table_id: init.table_id,
+                    player_names: init.player_names.clone(),
+                    our_index: game.our_index(),
+                });
+                self.game = Some(game);
+                self.handle.send_command(&client::GetGameInfo2 {
+                    table_id: init.table_id,
+                });
             }
+            Err(e) => tracing::error!("can't play at table: {e}"),
         }
-        Continue(())
     }
-    fn start(&mut self) {
-        if let Some(current_table) = self.current_table {
-            self.handle.send_command(&client::TableStart {
-                table_id: current_table,
+    fn game_actions(
+        &mut self,
+        table_id: TableID,
+        actions: Vec<server::GameActionType>,
+    ) {
+        let Some(game) = self.game.as_mut() else {
+            return;
+        };
+        if game.table_id != table_id || game.is_finished() {
+            return;
+        }
+        for action in actions {
+            if let Err(e) = game.apply(action) {
+                tracing::error!("stopped playing: {e}");
+                self.game = None;
+                return;
+            }
+        }
+        if game.is_finished() {
+            self.handle.metrics.game_over(game.score(), game.strikes());
+            self.handle.emit(Event::GameOver {
+                table_id,
+                score: game.score(),
             });
+            return;
+        }
+        for note in game.note_updates() {
+            self.handle.send_command(&note);
+        }
+        if game.is_our_turn() {
+            let started = Instant::now();
+            let decided = game.decide();
+            self.handle.metrics.decided(started.elapsed());
+            self.handle.send_command(&decided.action);
+            self.handle.emit(Event::ActionTaken {
+                table_id,
+                turn: decided.turn,
+                explanation: decided.explanation.clone(),
+            });
+            game.sent(decided);
         }
     }
+    // In daemon mode, leave a finished game so we're free for the next one
+    fn return_to_lobby(&mut self) {
+        if !self.daemon
+            || !self.game.as_ref().is_some_and(LiveGame::is_finished)
+        {
+            return;
+        }
+        if let Lobby::InGame { table_id, .. } = self.lobby {
+            self.handle
+                .send_command(&client::TableUnattend { table_id });
+            self.left();
+            self.handle.emit(Event::Left { table_id });
+        }
+    }
+    // Post our reasoning to the table once the game is over
+    fn explain_game(&self) {
+        let Some(game) = &self.game else {
+            return;
+        };
+        if !self.explain || !game.is_finished() {
+            return;
+        }
+        for (turn, explanation) in game.explanations() {
+            self.handle.send_command(&client::Chat {
+                msg: format!("turn {turn}: {explanation}"),
+                room: format!("table{}", game.table_id),
+            });
+        }
+    }
+    fn why(&self, turn: usize, who: String) {
+        let msg = self.game.as_ref().map_or_else(
+            || "I haven't played a game yet".to_owned(),
+            |game| {
+                game.explanation(turn).map_or_else(
+                    || format!("I didn't make a move on turn {turn}"),
+                    |explanation| format!("turn {turn}: {explanation}"),
+                )
+            },
+        );
+        self.send_pm(who, msg);
+    }
+    fn send_pm(&self, recipient: String, msg: String) {
+        self.handle.send_command(&client::ChatPM {
+            msg,
+            recipient,
+            room: "lobby".to_owned(),
+        });
+    }
 }
 
 #[derive(Debug)]
 enum Call {
-    CreateTable(client::TableCreate),
-    JoinTable(String),
-    FollowUser(String),
-    Start,
+    CreateTable(client::TableCreate, Reply),
+    JoinTable(String, Reply),
+    FollowUser(String, Reply),
+    Start(Reply),
+    Leave(Reply),
+    Explain(bool),
+    Daemon(bool),
+    Status(oneshot::Sender<Status>),
+    Lobby(oneshot::Sender<LobbySnapshot>),
+    // Leave our table and disconnect, PMing the farewell to whoever brought
+    // us to the table. The bot's future resolves once the connection closes.
+    Shutdown(Option<String>),
+}
+
+// What a bot is doing, for whoever is steering it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub username: String,
+    // idle, creating, joining, following, seated, playing or spectating
+    pub lobby: &'static str,
+    pub table_id: Option<TableID>,
+    // User whose table the bot keeps going to
+    pub following: Option<String>,
+    pub daemon: bool,
+}
+
+// The hanab.live lobby as a bot sees it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LobbySnapshot {
+    // By name
+    pub tables: Vec<LobbyTable>,
+    // Online but not at a table
+    pub idle_users: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LobbyTable {
+    pub table_id: TableID,
+    pub name: String,
+    // Seated or spectating
+    pub users: Vec<String>,
+}
+
+fn local(reply: Reply) -> Pending {
+    Pending::new(Requester::Local, Some(reply))
 }
 
 impl State {
     fn call(&mut self, call: Call) {
         match call {
-            Call::CreateTable(table) => self.create_table(table),
-            Call::JoinTable(s) => self.join_table(s),
-            Call::FollowUser(s) => self.follow_user(s),
-            Call::Start => self.start(),
+            Call::CreateTable(table, reply) => {
+                self.create_table(&table, local(reply));
+            }
+            Call::JoinTable(s, reply) => self.join_table(s, local(reply)),
+            Call::FollowUser(s, reply) => self.follow_user(s, local(reply)),
+            Call::Start(reply) => self.start(local(reply)),
+            Call::Leave(reply) => self.leave(local(reply)),
+            Call::Explain(explain) => self.explain = explain,
+            Call::Daemon(daemon) => self.daemon = daemon,
+            Call::Status(reply) => {
+                let _ = reply.send(self.status());
+            }
+            Call::Lobby(reply) => {
+                let _ = reply.send(self.lobby_snapshot());
+            }
+            Call::Shutdown(farewell) => self.shut_down(farewell),
+        }
+    }
+    // Leave our table so nobody is left waiting on us, then close the
+    // connection. Pending requests are dropped, which fails them with
+    // LobbyError::Stopped.
+    fn shut_down(&mut self, farewell: Option<String>) {
+        if let Some(table_id) = self.lobby.table_id() {
+            let host = self
+                .lobby
+                .following()
+                .map(ToOwned::to_owned)
+                .or_else(|| self.invited_by.clone());
+            if let (Some(host), Some(farewell)) = (host, farewell) {
+                self.send_pm(host, farewell);
+            }
+            if matches!(self.lobby, Lobby::Seated { .. }) {
+                self.handle.send_command(&client::TableLeave { table_id });
+            } else {
+                self.handle
+                    .send_command(&client::TableUnattend { table_id });
+            }
+            self.handle.emit(Event::Left { table_id });
         }
+        self.lobby = Lobby::Idle;
+        self.handle.transport.close();
+    }
+    fn status(&self) -> Status {
+        Status {
+            username: self.username().to_owned(),
+            lobby: self.lobby.name(),
+            table_id: self.lobby.table_id(),
+            following: self.lobby.following().map(ToOwned::to_owned),
+            daemon: self.daemon,
+        }
+    }
+    fn lobby_snapshot(&self) -> LobbySnapshot {
+        let mut tables: Vec<_> = self
+            .tables
+            .values()
+            .map(|table| {
+                let mut users: Vec<_> = self
+                    .users_at_table(table.id)
+                    .map(|user| user.name.clone())
+                    .collect();
+                users.sort();
+                LobbyTable {
+                    table_id: table.id,
+                    name: table.name.clone(),
+                    users,
+                }
+            })
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut idle_users: Vec<_> = self
+            .users
+            .values()
+            .filter(|user| user.table_id.is_none())
+            .map(|user| user.name.clone())
+            .collect();
+        idle_users.sort();
+        LobbySnapshot { tables, idle_users }
     }
     fn chat(&mut self, msg: &str, who: String) {
         if !msg.starts_with('/') {
             return;
         }
+        if !self.access.allows(&who) {
+            tracing::info!("refused {msg:?} from {who}");
+            self.send_pm(who, self.access.refusal(None));
+            return;
+        }
         let args = msg.split_whitespace();
         let result = ChatCommand::try_parse_from(args);
+        let request = Pending::new(Requester::User(who.clone()), None);
         match result {
-            Ok(_) => todo!(),
-            Err(_) => todo!(),
+            Ok(command) if !self.access.permits(&who, &command) => {
+                tracing::info!("refused {msg:?} from {who}");
+                self.send_pm(who, self.access.refusal(Some(&command)));
+            }
+            Ok(ChatCommand::Join { password }) => {
+                self.join_user_table(&who, password, request);
+            }
+            Ok(ChatCommand::Leave) => self.leave(request),
+            Ok(ChatCommand::Create {
+                table_name,
+                max_players,
+                password,
+            }) => {
+                let table = client::TableCreate {
+                    name: table_name,
+                    max_players,
+                    password,
+                };
+                self.create_table(&table, request);
+            }
+            Ok(ChatCommand::Start) => self.start(request),
+            Ok(ChatCommand::Why { turn }) => self.why(turn, who),
+            // clap's first line says what was wrong; the rest is usage,
+            // which is too long for a PM
+            Err(e) => {
+                let rendered = e.render().to_string();
+                let problem = rendered.lines().next().unwrap_or_default();
+                let hint = ChatCommand::hint();
+                self.send_pm(who, format!("{problem} ({hint})"));
+            }
         }
     }
 }
 
-#[async_trait]
-impl ezsockets::ClientExt for State {
-    type Call = Call;
+impl State {
+    // Handle messages and calls until the connection closes
+    async fn run(
+        mut self,
+        mut incoming: mpsc::UnboundedReceiver<Incoming>,
+        mut calls: mpsc::UnboundedReceiver<Call>,
+    ) -> eyre::Result<()> {
+        // Expires pending lobby requests
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                message = incoming.recv() => match message {
+                    Some(Incoming::Text(text)) => {
+                        self.handle.metrics.command_received(&text);
+                        self.handle.record(|| Frame::In(text.clone()));
+                        self.on_text(&text)?;
+                    }
+                    Some(Incoming::Binary(bytes)) => bail!(
+                        "received {} byte binary message from server",
+                        bytes.len()
+                    ),
+                    Some(Incoming::Error(e)) => return Err(e),
+                    None => return Ok(()),
+                },
+                Some(call) = calls.recv() => {
+                    let shutdown = matches!(call, Call::Shutdown(_));
+                    self.on_call(call);
+                    if shutdown {
+                        // Anything else the server sends is ignored
+                        let closed = async {
+                            while incoming.recv().await.is_some() {}
+                        };
+                        let _ = tokio::time::timeout(CLOSE_TIMEOUT, closed)
+                            .await;
+                        return Ok(());
+                    }
+                }
+                _ = interval.tick() => self.check_timeouts(),
+            }
+            self.handle.metrics.table(self.lobby.table_id());
+        }
+    }
 
-    #[instrument(skip_all, fields(username = self.username()))]
-    async fn on_text(&mut self, text: String) -> Result<(), ezsockets::Error> {
-        command::Parse::from_str(&text)
-            .handle_command(|server::Warning { warning }| {
+    #[instrument(skip_all, fields(table_id = self.table_id()))]
+    fn on_text(&mut self, text: &str) -> eyre::Result<()> {
+        use server::ServerMessage as M;
+        // A message we can't make sense of is skipped, since the server
+        // carries on either way
+        let message = match ServerMessage::parse(text) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!("skipping message from server: {e:#}");
+                return Ok(());
+            }
+        };
+        match message {
+            M::Warning(server::Warning { warning }) => {
                 tracing::warn!("received warning from server: {warning:?}");
-            })
-            .handle_command_result(|server::Error { error }| {
-                Err(eyre!("received error from server: {error}"))
-            })
-            .handle_command(|welcome: server::Welcome| {
+                if self.lobby.awaiting_answer() {
+                    self.fail_pending(LobbyError::Warning(warning));
+                }
+            }
+            M::Error(server::Error { error }) => {
+                self.handle.metrics.server_error();
+                if !self.fail_pending(LobbyError::Server(error.clone())) {
+                    bail!("received error from server: {error}");
+                }
+            }
+            M::Welcome(welcome) => {
                 tracing::info!("received welcome from server: {welcome:#?}");
-            })
-            .handle_command(|_: server::Name| {
-                // ignored
-            })
-            .handle_command(|user: server::User| {
-                self.insert_user(user);
-            })
-            .handle_command(|server::UserList(user_list)| {
+            }
+            M::User(user) => self.insert_user(user),
+            M::UserList(server::UserList(user_list)) => {
                 for user in user_list {
                     self.insert_user(user);
                 }
-            })
-            .handle_command(|server::UserLeft { user_id }| {
+            }
+            M::UserLeft(server::UserLeft { user_id }) => {
                 self.remove_user(user_id);
-            })
-            .handle_command(|table: server::Table| {
-                self.insert_table(table);
-            })
-            .handle_command(|server::TableList(list)| {
+            }
+            M::Table(table) => self.insert_table(table),
+            M::TableList(server::TableList(list)) => {
                 for table in list {
                     self.insert_table(table);
                 }
-            })
-            .handle_command(|server::TableGone { table_id }| {
+            }
+            M::TableGone(server::TableGone { table_id }) => {
                 self.remove_table(table_id);
-            })
-            .handle_command(
-                |server::Chat {
-                     msg,
-                     who,
-                     recipient,
-                 }| {
-                    if recipient.is_some_and(|x| x == self.username()) {
-                        self.chat(&msg, who);
-                    }
-                },
-            )
-            .handle_command(|server::Joined { table_id }| {
-                self.set_current_table(Some(table_id));
-            })
-            .handle_command(|server::Left { .. }| {
-                self.set_current_table(None);
-            })
-            .unhandled(|name, _data| {
+            }
+            M::Chat(server::Chat {
+                msg,
+                who,
+                recipient,
+            }) => {
+                self.handle.emit(Event::ChatReceived {
+                    who: who.clone(),
+                    msg: msg.clone(),
+                    recipient: recipient.clone(),
+                });
+                if recipient.is_some_and(|x| x == self.username()) {
+                    self.chat(&msg, who);
+                }
+            }
+            M::Joined(server::Joined { table_id }) => self.joined(table_id),
+            M::Left(server::Left { table_id }) => {
+                self.left();
+                self.handle.emit(Event::Left { table_id });
+            }
+            M::TableStart(server::TableStart { table_id }) => {
+                self.handle.send_command(&client::GetGameInfo1 { table_id });
+            }
+            M::Init(init) => self.init_game(&init),
+            M::GameActionList(server::GameActionList { table_id, list }) => {
+                self.game_actions(table_id, list);
+                self.handle.send_command(&client::Loaded { table_id });
+            }
+            M::GameAction(server::GameAction { table_id, action }) => {
+                let game_over =
+                    matches!(action, server::GameActionType::GameOver);
+                self.game_actions(table_id, vec![action]);
+                if game_over {
+                    self.explain_game();
+                    self.return_to_lobby();
+                }
+            }
+            M::Clock(clock) => {
+                if let Some(game) =
+                    self.game.as_mut().filter(|x| x.table_id == clock.table_id)
+                {
+                    game.set_clock(&clock);
+                }
+            }
+            M::NoteListPlayer(server::NoteListPlayer { table_id, notes }) => {
+                if let Some(game) =
+                    self.game.as_mut().filter(|x| x.table_id == table_id)
+                {
+                    game.set_notes(notes);
+                }
+            }
+            // ignored
+            M::Name(_)
+            | M::TableProgress(_)
+            | M::GameHistory(_)
+            | M::ChatList(_)
+            | M::ChatTyping(_)
+            | M::DatabaseID(_)
+            | M::Connected(_) => {}
+            M::Unknown { name, raw } => {
                 tracing::info!("received unhandled command {name:?}");
-                Ok(())
-            })
-            .map_err(Into::into)
-    }
-
-    async fn on_binary(
-        &mut self,
-        _bytes: Vec<u8>,
-    ) -> Result<(), ezsockets::Error> {
-        Err(eyre!("received binary message from server").into())
+                tracing::debug!("{name} payload: {raw}");
+            }
+        }
+        Ok(())
     }
 
-    #[instrument(skip_all, fields(username = self.username()))]
-    async fn on_call(
-        &mut self,
-        call: Self::Call,
-    ) -> Result<(), ezsockets::Error> {
+    #[instrument(skip_all, fields(table_id = self.table_id()))]
+    fn on_call(&mut self, call: Call) {
+        self.handle.record(|| Frame::Call(RecordedCall::new(&call)));
         self.call(call);
-        Ok(())
     }
 }
 
 mod bot {
     use super::*;
 
-    // Wraps the ezsockets::Client, simplifying the available functionality
-    #[derive(Debug)]
+    // Handle for driving a running bot. Clones drive the same bot.
+    #[derive(Debug, Clone)]
     pub struct Bot {
         // Informational
         pub username: String,
-        inner: ezsockets::Client<State>,
+        calls: mpsc::UnboundedSender<Call>,
+        events: broadcast::Sender<Event>,
     }
 
     impl Bot {
         #[allow(clippy::missing_const_for_fn)]
-        pub(super) fn from_handle(
+        pub(super) fn from_senders(
             username: String,
-            handle: ezsockets::Client<State>,
+            calls: mpsc::UnboundedSender<Call>,
+            events: broadcast::Sender<Event>,
         ) -> Self {
             Self {
                 username,
-                inner: handle,
+                calls,
+                events,
             }
         }
-        pub(super) fn send_command<T>(&self, command: &T)
-        where
-            T: Command + Serialize,
-        {
-            self.inner.text(command.serialize_command());
+        pub(super) fn call(&self, message: Call) {
+            if self.calls.send(message).is_err() {
+                tracing::warn!("{} is no longer running", self.username);
+            }
         }
-        pub(super) fn call(
-            &self,
-            message: <State as ezsockets::ClientExt>::Call,
-        ) {
-            self.inner.call(message);
+        pub(super) fn events(&self) -> broadcast::Receiver<Event> {
+            self.events.subscribe()
         }
     }
 }
 
 pub use bot::Bot;
 
+const EVENT_CAPACITY: usize = 256;
+// How long the server gets to acknowledge a close
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How bots connect. The same for every bot in a run.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    pub transport: TransportKind,
+    pub recording: Option<Recording>,
+    pub sessions: Option<SessionCache>,
+}
+
+async fn connect(
+    config: &BotConfig,
+    transport: TransportKind,
+    sessions: Option<&SessionCache>,
+) -> eyre::Result<Connection> {
+    let server = &config.server;
+    if let Some(cookie) =
+        sessions.and_then(|x| x.load(server, &config.username))
+    {
+        match transport.connect(server, cookie).await {
+            Ok(connection) => return Ok(connection),
+            // Most likely the session expired. Logging in replaces it.
+            Err(e) if transport::is_rejected(&e) => {
+                tracing::info!(
+                    "cached session rejected, logging in again: {e}"
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let cookie = log_in(config, sessions).await?;
+    transport.connect(server, cookie).await
+}
+
+// Get a new session, replacing the cached one
+async fn log_in(
+    config: &BotConfig,
+    sessions: Option<&SessionCache>,
+) -> eyre::Result<http::HeaderValue> {
+    if let Some(sessions) = sessions {
+        sessions.remove(&config.server, &config.username);
+    }
+    let cookie = hanab_live::authenticate_and_get_cookie(
+        &config.server,
+        &config.username,
+        config.password.expose(),
+    )
+    .await?;
+    if let Some(sessions) = sessions {
+        if let Err(e) =
+            sessions.store(&config.server, &config.username, &cookie)
+        {
+            tracing::warn!("couldn't cache session: {e:#}");
+        }
+    }
+    Ok(cookie)
+}
+
 impl Bot {
     // Construct a Bot. It runs as a spawned task.
     // Returns (bot, future)
     // where future is a JoinHandle for the task running the bot.
-    #[instrument(skip(password))]
+    // With a session cache, a cached session is tried before logging in.
+    #[instrument(skip_all, fields(username = config.username))]
     pub async fn new(
-        username: &str,
-        password: &str,
+        config: &BotConfig,
+        options: &ConnectOptions,
     ) -> eyre::Result<(Self, impl Future<Output = eyre::Result<()>>)> {
-        let cookie = authenticate_and_get_cookie(username, password).await?;
-
-        let config = ezsockets::ClientConfig::new("wss://hanab.live/ws")
-            .header(http::header::COOKIE, cookie);
-        // TODO ezsockets is a really small hobby crate.
-        // Maybe use a different websocket client library.
-        // Maybe pull directly from Github so new fixes are brought in
-        // immediately.
-        let username1 = username.to_owned();
-        let (handle, future) =
-            ezsockets::connect(|handle| State::new(username1, handle), config)
-                .await;
-        // No need for spawn, ezsockets already spawns
-        let future = future.map_err(|e| eyre!(e));
-        Ok((Self::from_handle(username.to_owned(), handle), future))
+        let connection =
+            connect(config, options.transport, options.sessions.as_ref())
+                .await?;
+        Ok(Self::from_connection(
+            &config.username,
+            config.strategy,
+            config.access.clone(),
+            connection,
+            options.recording.clone(),
+        ))
+    }
+
+    // Run a Bot over an already open connection, e.g. a Memory transport
+    pub fn from_connection(
+        username: &str,
+        strategy: StrategyKind,
+        access: Access,
+        connection: Connection,
+        recording: Option<Recording>,
+    ) -> (Self, impl Future<Output = eyre::Result<()>>) {
+        let (tx, calls) = mpsc::unbounded_channel();
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let state = State::new(
+            username.to_owned(),
+            connection.transport,
+            events.clone(),
+            recording,
+            strategy,
+            access,
+        );
+        let disconnected = events.clone();
+        let metrics = state.handle.metrics.clone();
+        // Not inside whatever span the caller is in, since the bot outlives it
+        let span = tracing::info_span!(parent: None, "bot", username);
+        let task = tokio::spawn(
+            async move {
+                metrics.connected(true);
+                let result = state.run(connection.incoming, calls).await;
+                metrics.connected(false);
+                metrics.table(None);
+                let _ = disconnected.send(Event::Disconnected {
+                    error: result.as_ref().err().map(ToString::to_string),
+                });
+                result
+            }
+            .instrument(span),
+        );
+        let future = async move { task.await? };
+        (Self::from_senders(username.to_owned(), tx, events), future)
+    }
+
+    // Everything the bot sees and does from now on, ending with
+    // Event::Disconnected. A subscriber that falls more than EVENT_CAPACITY
+    // events behind skips the oldest.
+    pub fn subscribe(&self) -> impl Stream<Item = Event> {
+        stream::unfold(Some(self.events()), |events| async move {
+            let mut events = events?;
+            loop {
+                match events.recv().await {
+                    Ok(event @ Event::Disconnected { .. }) => {
+                        return Some((event, None));
+                    }
+                    Ok(event) => return Some((event, Some(events))),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("subscriber skipped {skipped} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    // Each request resolves with the table we end up at, or why we didn't.
+    // The request is sent right away, so the future can be dropped if the
+    // outcome doesn't matter.
+    fn request(
+        &self,
+        call: impl FnOnce(Reply) -> Call,
+    ) -> impl Future<Output = Result<TableID, LobbyError>> {
+        let (tx, rx) = oneshot::channel();
+        self.call(call(tx));
+        rx.unwrap_or_else(|_| Err(LobbyError::Stopped))
     }
 
     // Create table. The server will automatically join this bot to the created table
-    pub fn create_table(&self, table: client::TableCreate) {
-        self.call(Call::CreateTable(table));
+    pub fn create_table(
+        &self,
+        table: client::TableCreate,
+    ) -> impl Future<Output = Result<TableID, LobbyError>> {
+        self.request(|reply| Call::CreateTable(table, reply))
     }
 
-    pub fn join_table(&self, table_name: String) {
-        self.call(Call::JoinTable(table_name));
+    pub fn join_table(
+        &self,
+        table_name: String,
+    ) -> impl Future<Output = Result<TableID, LobbyError>> {
+        self.request(|reply| Call::JoinTable(table_name, reply))
     }
 
-    pub fn follow_user(&self, username: String) {
-        self.call(Call::FollowUser(username));
+    // Resolves on the first join. The bot keeps following after that, and
+    // even if that join fails.
+    pub fn follow_user(
+        &self,
+        username: String,
+    ) -> impl Future<Output = Result<TableID, LobbyError>> {
+        self.request(|reply| Call::FollowUser(username, reply))
     }
 
-    // Start the current table
-    pub fn start(&self) {
-        self.call(Call::Start);
+    // Start the current table. Resolves once the game has started.
+    pub fn start(&self) -> impl Future<Output = Result<TableID, LobbyError>> {
+        self.request(Call::Start)
     }
-}
 
-// Authenticate to hanab.live, grab cookie from response
-async fn authenticate_and_get_cookie(
-    username: &str,
-    password: &str,
-) -> eyre::Result<http::HeaderValue> {
-    let url = Url::parse("https://hanab.live/login")?;
-
-    // Temporary client
-    let jar = Arc::new(reqwest::cookie::Jar::default());
-    let client = reqwest::ClientBuilder::new()
-        .cookie_provider(jar.clone())
-        .build()?;
-    let response = client
-        .post(url.clone())
-        .form(&json!({
-            "username": username,
-            "password": password,
-            "version": "bot",
-        }))
-        .send()
-        .await?;
-    response.error_for_status_ref().wrap_err_with(|| {
-        format!("Authentication failed. Server response: {response:#?}")
-    })?;
-    let cookie = jar.cookies(&url).ok_or_else(|| {
-        eyre!("No cookie was received from hanab.live server")
-    })?;
-    Ok(cookie)
+    // Leave the current table, or stop watching it. Fails during a game we're
+    // playing in.
+    pub fn leave(&self) -> impl Future<Output = Result<TableID, LobbyError>> {
+        self.request(Call::Leave)
+    }
+
+    // None if the bot is no longer running
+    pub fn status(&self) -> impl Future<Output = Option<Status>> {
+        let (tx, rx) = oneshot::channel();
+        self.call(Call::Status(tx));
+        rx.map(Result::ok)
+    }
+
+    // None if the bot is no longer running
+    pub fn lobby(&self) -> impl Future<Output = Option<LobbySnapshot>> {
+        let (tx, rx) = oneshot::channel();
+        self.call(Call::Lobby(tx));
+        rx.map(Result::ok)
+    }
+
+    // Leave the current table and disconnect. The farewell is PMed to the
+    // user the bot is following or who invited it to the table, if any. The
+    // bot's future resolves once the connection is closed.
+    pub fn shutdown(&self, farewell: Option<String>) {
+        self.call(Call::Shutdown(farewell));
+    }
+
+    // Whether to post decision explanations to table chat after each game
+    pub fn explain(&self, explain: bool) {
+        self.call(Call::Explain(explain));
+    }
+
+    // Whether to go back to the lobby after each game, to be invited to the
+    // next one with /join
+    pub fn daemon(&self, daemon: bool) {
+        self.call(Call::Daemon(daemon));
+    }
 }
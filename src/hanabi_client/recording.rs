@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{self, WrapErr};
+use hanab_live::command::{client, Command};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use super::Call;
+
+// A JSONL file of everything that went over the wire, shared by all bots
+// that record into it. Bot calls and request timeouts are recorded too, so
+// the session can be replayed without the server.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    file: Arc<Mutex<LineWriter<File>>>,
+}
+
+// Stands in for passwords, which are never written to a recording
+const REDACTED: &str = "[redacted]";
+
+// One line of a recording
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    // Milliseconds since the Unix epoch
+    pub time_ms: u64,
+    pub username: String,
+    #[serde(flatten)]
+    pub frame: Frame,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "camelCase")]
+pub enum Frame {
+    // Websocket text from the server
+    In(String),
+    // Websocket text to the server
+    Out(String),
+    Call(RecordedCall),
+    // The pending lobby request timed out
+    Expired,
+}
+
+// A Call, minus the reply channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordedCall {
+    CreateTable {
+        name: Option<String>,
+        max_players: u8,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    JoinTable(String),
+    FollowUser(String),
+    Start,
+    Leave,
+    Explain(bool),
+    Daemon(bool),
+    Status,
+    Lobby,
+    Shutdown(Option<String>),
+}
+
+impl Recording {
+    pub fn create(path: &Path) -> eyre::Result<Self> {
+        let file = File::create(path)
+            .wrap_err_with(|| format!("creating {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(LineWriter::new(file))),
+        })
+    }
+
+    pub fn record(&self, username: &str, frame: Frame) {
+        let time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| u64::try_from(x.as_millis()).unwrap_or(u64::MAX));
+        let entry = Entry {
+            time_ms,
+            username: username.to_owned(),
+            frame,
+        };
+        let mut line = serde_json::to_string(&entry).unwrap();
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            tracing::error!("couldn't write to recording: {e}");
+        }
+    }
+}
+
+// Outgoing text as it's recorded, with any table password replaced
+pub fn redact(text: &str) -> String {
+    let Some((name, data)) = text.split_once(' ') else {
+        return text.to_owned();
+    };
+    if name != client::TableCreate::NAME && name != client::TableJoin::NAME {
+        return text.to_owned();
+    }
+    let Ok(mut data) = serde_json::from_str::<serde_json::Value>(data) else {
+        return text.to_owned();
+    };
+    match data.get_mut("password") {
+        Some(password) if !password.is_null() => {
+            *password = REDACTED.into();
+            format!("{name} {data}")
+        }
+        _ => text.to_owned(),
+    }
+}
+
+impl RecordedCall {
+    pub(super) fn new(call: &Call) -> Self {
+        match call {
+            Call::CreateTable(table, _) => Self::CreateTable {
+                name: table.name.clone(),
+                max_players: table.max_players,
+                password: table.password.as_ref().map(|_| REDACTED.to_owned()),
+            },
+            Call::JoinTable(table_name, _) => {
+                Self::JoinTable(table_name.clone())
+            }
+            Call::FollowUser(username, _) => Self::FollowUser(username.clone()),
+            Call::Start(_) => Self::Start,
+            Call::Leave(_) => Self::Leave,
+            Call::Explain(explain) => Self::Explain(*explain),
+            Call::Daemon(daemon) => Self::Daemon(*daemon),
+            Call::Status(_) => Self::Status,
+            Call::Lobby(_) => Self::Lobby,
+            Call::Shutdown(farewell) => Self::Shutdown(farewell.clone()),
+        }
+    }
+
+    // Nobody is waiting on the outcome of a replayed call
+    pub(super) fn into_call(self) -> Call {
+        let (reply, _) = oneshot::channel();
+        match self {
+            Self::CreateTable {
+                name,
+                max_players,
+                password,
+            } => Call::CreateTable(
+                client::TableCreate {
+                    name,
+                    max_players,
+                    password,
+                },
+                reply,
+            ),
+            Self::JoinTable(table_name) => Call::JoinTable(table_name, reply),
+            Self::FollowUser(username) => Call::FollowUser(username, reply),
+            Self::Start => Call::Start(reply),
+            Self::Leave => Call::Leave(reply),
+            Self::Explain(explain) => Call::Explain(explain),
+            Self::Daemon(daemon) => Call::Daemon(daemon),
+            Self::Status => Call::Status(oneshot::channel().0),
+            Self::Lobby => Call::Lobby(oneshot::channel().0),
+            Self::Shutdown(farewell) => Call::Shutdown(farewell),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_table_passwords() {
+        assert_eq!(
+            redact(r#"tableJoin {"tableID":5,"password":"hunter2"}"#),
+            r#"tableJoin {"tableID":5,"password":"[redacted]"}"#
+        );
+        assert_eq!(
+            redact(r#"tableJoin {"tableID":5}"#),
+            r#"tableJoin {"tableID":5}"#
+        );
+        let chat = r#"chat {"msg":"password","room":"lobby"}"#;
+        assert_eq!(redact(chat), chat);
+    }
+}
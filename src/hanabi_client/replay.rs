@@ -0,0 +1,125 @@
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use color_eyre::eyre::{self, bail, WrapErr};
+use tokio::sync::broadcast;
+
+use super::recording::{self, Entry, Frame};
+use super::transport::Memory;
+use super::{LobbyError, State, EVENT_CAPACITY};
+use crate::chat_command::Access;
+use crate::config::Config;
+use crate::strategy::StrategyKind;
+
+// Feed a recording back through the bot and check it sends what it sent
+// the first time
+#[derive(Debug, clap::Args)]
+pub struct ReplayArgs {
+    // JSONL file written with --record
+    file: PathBuf,
+    // Only replay this bot. By default every bot in the recording is
+    // replayed.
+    #[arg(long, value_name = "USERNAME")]
+    user: Option<String>,
+    // Must be the strategy the bots were recorded with
+    #[arg(short, long, value_enum, default_value_t = StrategyKind::Basic)]
+    strategy: StrategyKind,
+    // Config the bots were recorded with, for who may give them chat
+    // commands. Without it, anyone may.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+pub fn run(args: &ReplayArgs) -> eyre::Result<()> {
+    let config = args.config.as_deref().map(Config::load).transpose()?;
+    let f = std::fs::File::open(&args.file)
+        .wrap_err_with(|| format!("reading {}", args.file.display()))?;
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let entry: Entry = serde_json::from_str(&line?)
+            .wrap_err_with(|| format!("line {}", i + 1))?;
+        entries.push(entry);
+    }
+    let mut usernames: Vec<&str> =
+        entries.iter().map(|x| x.username.as_str()).collect();
+    usernames.sort_unstable();
+    usernames.dedup();
+    if let Some(user) = &args.user {
+        usernames.retain(|x| x == user);
+        if usernames.is_empty() {
+            bail!("{user} isn't in the recording");
+        }
+    }
+    for username in usernames {
+        let frames = entries
+            .iter()
+            .filter(|x| x.username == username)
+            .map(|x| &x.frame);
+        let access = config
+            .as_ref()
+            .map_or_else(Access::default, |x| x.access(username));
+        replay_bot(username, args.strategy, access, frames)?;
+    }
+    Ok(())
+}
+
+fn replay_bot<'a>(
+    username: &str,
+    strategy: StrategyKind,
+    access: Access,
+    frames: impl Iterator<Item = &'a Frame>,
+) -> eyre::Result<()> {
+    let (connection, mut sent) = Memory::pair();
+    let (events, _) = broadcast::channel(EVENT_CAPACITY);
+    let mut state = State::new(
+        username.to_owned(),
+        connection.transport,
+        events,
+        None,
+        strategy,
+        access,
+    );
+    let mut recorded = Vec::new();
+    let mut replayed = Vec::new();
+    for frame in frames {
+        match frame {
+            Frame::In(text) => {
+                if let Err(e) = state.on_text(text) {
+                    tracing::warn!("{username}: replay stopped early: {e}");
+                    break;
+                }
+            }
+            Frame::Out(text) => recorded.push(text.as_str()),
+            Frame::Call(call) => {
+                state.call(call.clone().into_call());
+            }
+            Frame::Expired => {
+                state.fail_pending(LobbyError::Timeout);
+            }
+        }
+        while let Ok(text) = sent.try_recv() {
+            // Passwords were redacted when recording
+            replayed.push(recording::redact(&text));
+        }
+    }
+
+    for (i, (recorded, replayed)) in recorded.iter().zip(&replayed).enumerate()
+    {
+        if recorded != replayed {
+            bail!(
+                "{username}: outbound frame {} differs\n  recorded: \
+                 {recorded}\n  replayed: {replayed}",
+                i + 1
+            );
+        }
+    }
+    if recorded.len() != replayed.len() {
+        bail!(
+            "{username}: recorded {} outbound frames, replayed {}",
+            recorded.len(),
+            replayed.len()
+        );
+    }
+    println!("{username}: all {} outbound frames match", recorded.len());
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, WrapErr};
+use hanab_live::Url;
+
+// Session cookies from logging in, kept on disk by server and username so
+// bots can connect again without logging in. A cookie is as good as a
+// password while it lasts, so only the owner can read the files.
+#[derive(Debug, Clone)]
+pub struct SessionCache {
+    dir: PathBuf,
+}
+
+impl SessionCache {
+    pub const fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // Under the user's cache directory, if the platform has one
+    pub fn default_dir() -> Option<PathBuf> {
+        Some(dirs::cache_dir()?.join("hanab-live-bot").join("sessions"))
+    }
+
+    fn path(&self, server: &Url, username: &str) -> PathBuf {
+        let host = server.host_str().unwrap_or("unknown");
+        let host = server
+            .port()
+            .map_or_else(|| host.to_owned(), |port| format!("{host}_{port}"));
+        self.dir.join(host).join(file_name(username))
+    }
+
+    pub fn load(
+        &self,
+        server: &Url,
+        username: &str,
+    ) -> Option<http::HeaderValue> {
+        let cookie = fs::read_to_string(self.path(server, username)).ok()?;
+        http::HeaderValue::from_str(cookie.trim()).ok()
+    }
+
+    pub fn store(
+        &self,
+        server: &Url,
+        username: &str,
+        cookie: &http::HeaderValue,
+    ) -> eyre::Result<()> {
+        let path = self.path(server, username);
+        write_private(&path, cookie.as_bytes())
+            .wrap_err_with(|| format!("writing {}", path.display()))
+    }
+
+    // Forget a session the server no longer accepts
+    pub fn remove(&self, server: &Url, username: &str) {
+        let _ = fs::remove_file(self.path(server, username));
+    }
+}
+
+// Create or replace a file only the owner can read. The contents go to a
+// temporary file next to it first, so a crash never leaves it half written.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file")
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+    let result = write_new_private(&temp, contents)
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_new_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // A leftover file keeps its old mode
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+// Usernames can have characters that mean something in paths, so anything
+// other than letters, digits, - and _ is escaped
+fn file_name(username: &str) -> String {
+    let mut name = String::new();
+    for &byte in username.as_bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(char::from(byte));
+        } else {
+            let _ = write!(name, "%{byte:02X}");
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> Url {
+        "https://hanab.live/".parse().unwrap()
+    }
+
+    #[test]
+    fn stores_and_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SessionCache::new(dir.path().to_owned());
+        assert_eq!(cache.load(&server(), "bot/1"), None);
+        let cookie = http::HeaderValue::from_static("hanabi.sid=abc");
+        cache.store(&server(), "bot/1", &cookie).unwrap();
+        assert_eq!(cache.load(&server(), "bot/1"), Some(cookie));
+        // Other users and servers have their own sessions
+        assert_eq!(cache.load(&server(), "bot_1"), None);
+        let other = "http://localhost:8081/".parse().unwrap();
+        assert_eq!(cache.load(&other, "bot/1"), None);
+        cache.remove(&server(), "bot/1");
+        assert_eq!(cache.load(&server(), "bot/1"), None);
+    }
+
+    #[test]
+    fn corrupt_session_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SessionCache::new(dir.path().to_owned());
+        let path = cache.path(&server(), "bot");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "hanabi.sid=\x00\n").unwrap();
+        assert_eq!(cache.load(&server(), "bot"), None);
+        fs::write(&path, [0xff, 0xfe]).unwrap();
+        assert_eq!(cache.load(&server(), "bot"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_private_restricts_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Nothing left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,388 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hanab_live::command::client;
+use hanab_live::Url;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::server::{
+    ErrorResponse, Request, Response,
+};
+
+use super::transport::{Connection, Incoming, Memory, TransportKind};
+use super::{connect, Bot, LobbyError, Recording, SessionCache};
+use crate::chat_command::Access;
+use crate::config::BotConfig;
+use crate::strategy::StrategyKind;
+
+// Stands in for hanab.live at the other end of a Memory transport
+struct Server {
+    // Weak, so that the bot closing the connection ends it
+    incoming: mpsc::WeakUnboundedSender<Incoming>,
+    sent: mpsc::UnboundedReceiver<String>,
+}
+
+impl Server {
+    fn pair() -> (Connection, Self) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let incoming = sender.downgrade();
+        let (connection, sent) = Memory::connect(sender, receiver);
+        (connection, Self { incoming, sent })
+    }
+
+    fn send_text(&self, text: &str) {
+        let incoming = self.incoming.upgrade().expect("bot closed");
+        let _ = incoming.send(Incoming::Text(text.to_owned()));
+    }
+
+    // Next message the bot sent. None once the bot is gone.
+    async fn recv(&mut self) -> Option<String> {
+        self.sent.recv().await
+    }
+}
+
+// A bot talking to a Server
+fn start() -> (Bot, Server) {
+    let (connection, server) = Server::pair();
+    let (bot, _) = Bot::from_connection(
+        "bot",
+        StrategyKind::Basic,
+        Access::default(),
+        connection,
+        None,
+    );
+    (bot, server)
+}
+
+// A bot recording to a file in dir
+fn start_recording(dir: &std::path::Path) -> (Bot, Server) {
+    let recording = Recording::create(&dir.join("recording.jsonl")).unwrap();
+    let (connection, server) = Server::pair();
+    let (bot, _) = Bot::from_connection(
+        "bot",
+        StrategyKind::Basic,
+        Access::default(),
+        connection,
+        Some(recording),
+    );
+    (bot, server)
+}
+
+// Everything the bot sends up to and including the next name command
+async fn sent_until(server: &mut Server, name: &str) -> Vec<String> {
+    let mut sent = Vec::new();
+    loop {
+        let text = tokio::time::timeout(Duration::from_secs(5), server.recv())
+            .await
+            .unwrap_or_else(|_| panic!("no {name} after {sent:?}"))
+            .expect("bot stopped");
+        let done = text.split_once(' ').is_some_and(|(x, _)| x == name);
+        sent.push(text);
+        if done {
+            return sent;
+        }
+    }
+}
+
+// The payload of the next name command
+async fn next(server: &mut Server, name: &str) -> serde_json::Value {
+    let sent = sent_until(server, name).await;
+    let (_, data) = sent.last().unwrap().split_once(' ').unwrap();
+    serde_json::from_str(data).unwrap()
+}
+
+fn count(sent: &[String], name: &str) -> usize {
+    sent.iter()
+        .filter(|x| x.split_once(' ').is_some_and(|(x, _)| x == name))
+        .count()
+}
+
+// A PM to the bot from alice
+fn pm(msg: &str) -> String {
+    format!(r#"chat {{"msg":"{msg}","who":"alice","recipient":"bot"}}"#)
+}
+
+#[tokio::test]
+async fn creates_table() {
+    let (bot, mut server) = start();
+    let created = bot.create_table(client::TableCreate {
+        name: Some("fun".to_owned()),
+        max_players: 2,
+        password: None,
+    });
+    let sent = next(&mut server, "tableCreate").await;
+    assert_eq!(sent["name"], "fun");
+    server.send_text(r#"joined {"tableID":5}"#);
+    assert_eq!(u64::from(created.await.unwrap()), 5);
+    let status = bot.status().await.unwrap();
+    assert_eq!(status.lobby, "seated");
+}
+
+#[tokio::test]
+async fn recording_leaves_out_passwords() {
+    let dir = tempfile::tempdir().unwrap();
+    let (bot, mut server) = start_recording(dir.path());
+    let created = bot.create_table(client::TableCreate {
+        name: Some("fun".to_owned()),
+        max_players: 2,
+        password: Some("hunter2".to_owned()),
+    });
+    let sent = next(&mut server, "tableCreate").await;
+    assert_eq!(sent["password"], "hunter2");
+    server.send_text(r#"joined {"tableID":5}"#);
+    created.await.unwrap();
+    let recorded =
+        std::fs::read_to_string(dir.path().join("recording.jsonl")).unwrap();
+    assert!(!recorded.contains("hunter2"), "{recorded}");
+    assert!(recorded.contains("[redacted]"), "{recorded}");
+}
+
+// A warning only answers a request once its command has been sent
+#[tokio::test]
+async fn fails_request_on_warning_after_join() {
+    let (bot, mut server) = start();
+    let joined = bot.join_table("fun".to_owned());
+    // Before the table exists, so before tableJoin
+    server.send_text(r#"warning {"warning":"You are chatting too fast."}"#);
+    server.send_text(r#"table {"id":7,"name":"fun"}"#);
+    next(&mut server, "tableJoin").await;
+    server.send_text(r#"warning {"warning":"That table is full."}"#);
+    assert_eq!(
+        joined.await,
+        Err(LobbyError::Warning("That table is full.".to_owned()))
+    );
+}
+
+#[tokio::test]
+async fn joins_table_by_name() {
+    let (bot, mut server) = start();
+    server.send_text(r#"table {"id":7,"name":"fun"}"#);
+    let joined = bot.join_table("fun".to_owned());
+    let sent = next(&mut server, "tableJoin").await;
+    assert_eq!(sent["tableID"], 7);
+    server.send_text(r#"joined {"tableID":7}"#);
+    assert_eq!(u64::from(joined.await.unwrap()), 7);
+}
+
+#[tokio::test]
+async fn follows_user() {
+    let (bot, mut server) = start();
+    let joined = bot.follow_user("alice".to_owned());
+    server.send_text(
+        r#"user {"userID":1,"name":"alice","status":0,"tableID":0}"#,
+    );
+    server.send_text(
+        r#"user {"userID":1,"name":"alice","status":1,"tableID":9}"#,
+    );
+    let sent = next(&mut server, "tableJoin").await;
+    assert_eq!(sent["tableID"], 9);
+    server.send_text(r#"joined {"tableID":9}"#);
+    assert_eq!(u64::from(joined.await.unwrap()), 9);
+    let status = bot.status().await.unwrap();
+    assert_eq!(status.following.as_deref(), Some("alice"));
+}
+
+// A refused join isn't retried until the user moves to another table
+#[tokio::test]
+async fn follow_retries_only_at_new_table() {
+    let (bot, mut server) = start();
+    let joined = bot.follow_user("alice".to_owned());
+    server.send_text(
+        r#"user {"userID":1,"name":"alice","status":1,"tableID":9}"#,
+    );
+    next(&mut server, "tableJoin").await;
+    server.send_text(r#"warning {"warning":"That table is full."}"#);
+    assert!(joined.await.is_err());
+    for _ in 0..3 {
+        server.send_text(
+            r#"user {"userID":1,"name":"alice","status":1,"tableID":9}"#,
+        );
+    }
+    server.send_text(
+        r#"user {"userID":1,"name":"alice","status":1,"tableID":10}"#,
+    );
+    let sent = sent_until(&mut server, "tableJoin").await;
+    assert_eq!(count(&sent, "tableJoin"), 1, "{sent:?}");
+    assert!(sent.last().unwrap().contains(r#""tableID":10"#), "{sent:?}");
+}
+
+// The bot is the second player. It acts once, on the server's turn action
+// for it, not as soon as alice's play or her draw arrives.
+#[tokio::test]
+async fn acts_once_per_turn() {
+    let (_bot, mut server) = start();
+    server.send_text(
+        r#"init {"tableID":5,"playerNames":["alice","bot"],"ourPlayerIndex":1,"spectating":false,"replay":false,"options":{"numPlayers":2,"variantName":"No Variant","timed":false,"timeBase":0,"timePerTurn":0}}"#,
+    );
+    next(&mut server, "getGameInfo2").await;
+    let mut deal = Vec::new();
+    for order in 0..5 {
+        deal.push(format!(
+            r#"{{"type":"draw","playerIndex":0,"order":{order},"suitIndex":{order},"rank":1}}"#
+        ));
+    }
+    for order in 5..10 {
+        deal.push(format!(
+            r#"{{"type":"draw","playerIndex":1,"order":{order},"suitIndex":-1,"rank":-1}}"#
+        ));
+    }
+    deal.push(r#"{"type":"turn","num":0,"currentPlayerIndex":0}"#.to_owned());
+    server.send_text(&format!(
+        r#"gameActionList {{"tableID":5,"list":[{}]}}"#,
+        deal.join(",")
+    ));
+    server.send_text(
+        r#"gameAction {"tableID":5,"action":{"type":"play","playerIndex":0,"order":0,"suitIndex":0,"rank":1}}"#,
+    );
+    server.send_text(
+        r#"gameAction {"tableID":5,"action":{"type":"draw","playerIndex":0,"order":10,"suitIndex":0,"rank":2}}"#,
+    );
+    server.send_text(
+        r#"gameAction {"tableID":5,"action":{"type":"turn","num":1,"currentPlayerIndex":1}}"#,
+    );
+    // Messages are handled in order, so the answer comes after anything
+    // the turn led to
+    server.send_text(&pm("/why 2"));
+    let sent = sent_until(&mut server, "chatPM").await;
+    assert_eq!(count(&sent, "action"), 1, "{sent:?}");
+    assert!(sent.last().unwrap().contains("turn 2: "), "{sent:?}");
+}
+
+#[tokio::test]
+async fn skips_unknown_and_malformed_messages() {
+    let (_bot, mut server) = start();
+    server.send_text(r#"somethingNew {"x":1}"#);
+    server.send_text(r#"tableGone {"tableID":"x"}"#);
+    server.send_text(r#"gameHistory [{"id":1}]"#);
+    server.send_text(&pm("/why 1"));
+    let sent = next(&mut server, "chatPM").await;
+    assert_eq!(sent["msg"], "I haven't played a game yet");
+}
+
+#[tokio::test]
+async fn shuts_down_promptly() {
+    let (connection, _server) = Server::pair();
+    let (bot, stopped) = Bot::from_connection(
+        "bot",
+        StrategyKind::Basic,
+        Access::default(),
+        connection,
+        None,
+    );
+    bot.shutdown(None);
+    tokio::time::timeout(Duration::from_secs(1), stopped)
+        .await
+        .expect("closing didn't end the connection")
+        .unwrap();
+}
+
+// A local server that logs anyone in with the cookie session=fresh, and
+// only accepts websockets with that cookie. Counts logins.
+async fn login_server() -> (Url, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let logins = Arc::new(AtomicUsize::new(0));
+    let counter = logins.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(serve_login(stream, counter.clone()));
+        }
+    });
+    (url.parse().unwrap(), logins)
+}
+
+async fn serve_login(mut stream: TcpStream, logins: Arc<AtomicUsize>) {
+    let mut method = [0; 4];
+    stream.peek(&mut method).await.unwrap();
+    if &method == b"POST" {
+        // The form is small enough to arrive along with the headers
+        let mut request = [0; 4096];
+        let _ = stream.read(&mut request).await.unwrap();
+        logins.fetch_add(1, Ordering::SeqCst);
+        let response = "HTTP/1.1 200 OK\r\nSet-Cookie: session=fresh; \
+                        Path=/\r\nContent-Length: 0\r\n\r\n";
+        stream.write_all(response.as_bytes()).await.unwrap();
+        return;
+    }
+    if let Ok(mut ws) =
+        tokio_tungstenite::accept_hdr_async(stream, check_cookie).await
+    {
+        use futures::StreamExt;
+        while ws.next().await.is_some() {}
+    }
+}
+
+// tungstenite decides the error type
+#[allow(clippy::result_large_err)]
+fn check_cookie(
+    request: &Request,
+    response: Response,
+) -> Result<Response, ErrorResponse> {
+    if request
+        .headers()
+        .get("cookie")
+        .is_some_and(|x| x == "session=fresh")
+    {
+        return Ok(response);
+    }
+    let mut rejection = ErrorResponse::new(None);
+    *rejection.status_mut() = http::StatusCode::UNAUTHORIZED;
+    Err(rejection)
+}
+
+fn bot_config(server: &Url) -> BotConfig {
+    BotConfig {
+        username: "bot".to_owned(),
+        password: serde_json::from_str(r#""hunter2""#).unwrap(),
+        server: server.clone(),
+        strategy: StrategyKind::Basic,
+        explain: false,
+        access: Access::default(),
+    }
+}
+
+#[tokio::test]
+async fn cached_session_is_reused() {
+    let (server, logins) = login_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let sessions = SessionCache::new(dir.path().to_owned());
+    let config = bot_config(&server);
+    for _ in 0..2 {
+        let tungstenite = TransportKind::Tungstenite;
+        connect(&config, tungstenite, Some(&sessions))
+            .await
+            .unwrap();
+    }
+    assert_eq!(logins.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn stale_session_logs_in_again() {
+    let (server, logins) = login_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let sessions = SessionCache::new(dir.path().to_owned());
+    let stale = http::HeaderValue::from_static("session=stale");
+    let config = bot_config(&server);
+    for transport in [TransportKind::Tungstenite, TransportKind::Ezsockets] {
+        sessions.store(&server, "bot", &stale).unwrap();
+        connect(&config, transport, Some(&sessions)).await.unwrap();
+        let cookie = sessions.load(&server, "bot").unwrap();
+        assert_eq!(cookie, "session=fresh");
+    }
+    assert_eq!(logins.load(Ordering::SeqCst), 2);
+}
+
+// As with --no-session-cache
+#[tokio::test]
+async fn without_cache_always_logs_in() {
+    let (server, logins) = login_server().await;
+    let config = bot_config(&server);
+    for _ in 0..2 {
+        let tungstenite = TransportKind::Tungstenite;
+        connect(&config, tungstenite, None).await.unwrap();
+    }
+    assert_eq!(logins.load(Ordering::SeqCst), 2);
+}
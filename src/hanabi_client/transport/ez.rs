@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+use color_eyre::eyre::{self, bail, eyre};
+use hanab_live::Url;
+use tokio::sync::{mpsc, oneshot};
+
+use super::{Connection, Incoming, Transport};
+
+// TODO ezsockets is a really small hobby crate.
+// Maybe pull directly from Github so new fixes are brought in immediately.
+#[derive(Debug)]
+pub struct Ezsockets {
+    inner: ezsockets::Client<Forwarder>,
+}
+
+// Hands everything ezsockets receives to the incoming stream
+#[derive(Debug)]
+struct Forwarder {
+    incoming: mpsc::UnboundedSender<Incoming>,
+    // Fired by the first successful handshake
+    connected: Option<oneshot::Sender<()>>,
+}
+
+#[async_trait]
+impl ezsockets::ClientExt for Forwarder {
+    type Call = ();
+
+    async fn on_text(&mut self, text: String) -> Result<(), ezsockets::Error> {
+        self.incoming
+            .send(Incoming::Text(text))
+            .map_err(|_| eyre!("connection dropped").into())
+    }
+
+    async fn on_binary(
+        &mut self,
+        bytes: Vec<u8>,
+    ) -> Result<(), ezsockets::Error> {
+        self.incoming
+            .send(Incoming::Binary(bytes))
+            .map_err(|_| eyre!("connection dropped").into())
+    }
+
+    async fn on_call(
+        &mut self,
+        (): Self::Call,
+    ) -> Result<(), ezsockets::Error> {
+        Ok(())
+    }
+
+    async fn on_connect(&mut self) -> Result<(), ezsockets::Error> {
+        if let Some(connected) = self.connected.take() {
+            let _ = connected.send(());
+        }
+        Ok(())
+    }
+
+    // ezsockets can't be told not to reconnect, and reconnects quietly with
+    // the same cookie and stale State. Failing here stops it instead, so the
+    // connection ends and whoever runs the bot decides what to do.
+    async fn on_close(&mut self) -> Result<(), ezsockets::Error> {
+        Err(Box::new(Closed))
+    }
+}
+
+// The server closed the connection. Not reported as an error, like a close
+// with other transports.
+#[derive(Debug)]
+struct Closed;
+
+impl std::fmt::Display for Closed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("connection closed")
+    }
+}
+
+impl std::error::Error for Closed {}
+
+impl Ezsockets {
+    pub async fn connect(
+        url: Url,
+        cookie: http::HeaderValue,
+    ) -> eyre::Result<Connection> {
+        let config = ezsockets::ClientConfig::new(url)
+            .header(http::header::COOKIE, cookie);
+        let (tx, incoming) = mpsc::unbounded_channel();
+        let (connected, handshake) = oneshot::channel();
+        let forwarder = Forwarder {
+            incoming: tx.clone(),
+            connected: Some(connected),
+        };
+        let (inner, future) = ezsockets::connect(|_| forwarder, config).await;
+        let mut future = Box::pin(future);
+        // ezsockets does the handshake in the background. Wait for it, so a
+        // rejected connection is an error here like with other transports.
+        tokio::select! {
+            Ok(()) = handshake => {}
+            result = &mut future => {
+                result.map_err(report)?;
+                bail!("connection closed during handshake");
+            }
+        }
+        // ezsockets already spawns the connection; this only reports how it
+        // ended
+        tokio::spawn(async move {
+            match future.await {
+                Err(e) if !e.is::<Closed>() => {
+                    let _ = tx.send(Incoming::Error(report(e)));
+                }
+                _ => {}
+            }
+        });
+        Ok(Connection {
+            transport: Box::new(Self { inner }),
+            incoming,
+        })
+    }
+}
+
+impl Transport for Ezsockets {
+    fn send(&self, text: String) {
+        self.inner.text(text);
+    }
+
+    fn close(&self) {
+        // Only queues the close frame, despite being async
+        tokio::spawn(self.inner.clone().close(None));
+    }
+}
+
+// Keeps tungstenite's errors as they are, so is_rejected can find them
+fn report(error: ezsockets::Error) -> eyre::Report {
+    match error.downcast::<tokio_tungstenite::tungstenite::Error>() {
+        Ok(error) => eyre::Report::new(*error),
+        Err(error) => eyre!(error),
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Mutex;
+
+use tokio::sync::mpsc;
+
+use super::{Connection, Incoming, Transport};
+
+// In-process transport, with the caller standing in for hanab.live. For
+// tests and replays.
+#[derive(Debug)]
+pub struct Memory {
+    outgoing: mpsc::UnboundedSender<String>,
+    // Dropped on close, which ends the incoming stream
+    incoming: Mutex<Option<mpsc::UnboundedSender<Incoming>>>,
+}
+
+impl Memory {
+    // A connection that nothing is sent to, and what it sends
+    pub fn pair() -> (Connection, mpsc::UnboundedReceiver<String>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self::connect(sender, receiver)
+    }
+
+    // A connection receiving from the channel, and what it sends. Whoever
+    // else sends on the channel should only keep a weak sender, or closing
+    // the connection can't end the stream.
+    pub fn connect(
+        sender: mpsc::UnboundedSender<Incoming>,
+        receiver: mpsc::UnboundedReceiver<Incoming>,
+    ) -> (Connection, mpsc::UnboundedReceiver<String>) {
+        let (outgoing, sent) = mpsc::unbounded_channel();
+        let connection = Connection {
+            transport: Box::new(Self {
+                outgoing,
+                incoming: Mutex::new(Some(sender)),
+            }),
+            incoming: receiver,
+        };
+        (connection, sent)
+    }
+}
+
+impl Transport for Memory {
+    fn send(&self, text: String) {
+        let _ = self.outgoing.send(text);
+    }
+
+    fn close(&self) {
+        self.incoming.lock().unwrap().take();
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::Debug;
+
+use color_eyre::eyre;
+use hanab_live::Url;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Error as WsError;
+
+mod ez;
+mod memory;
+mod tungstenite;
+
+pub use ez::Ezsockets;
+pub use memory::Memory;
+pub use tungstenite::Tungstenite;
+
+// The sending half of a websocket connection to hanab.live. State only talks
+// to the server through this, so the websocket library can be swapped out.
+pub trait Transport: Debug + Send {
+    fn send(&self, text: String);
+    // Start a clean close. The incoming stream ends once the server agrees.
+    fn close(&self);
+}
+
+// Received from the server
+#[derive(Debug)]
+pub enum Incoming {
+    Text(String),
+    Binary(Vec<u8>),
+    // The connection failed. The stream ends after this.
+    Error(eyre::Report),
+}
+
+// An open connection. The incoming stream ending means the connection closed.
+#[derive(Debug)]
+pub struct Connection {
+    pub transport: Box<dyn Transport>,
+    pub incoming: mpsc::UnboundedReceiver<Incoming>,
+}
+
+// Websocket library to connect with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TransportKind {
+    #[default]
+    Ezsockets,
+    Tungstenite,
+}
+
+impl TransportKind {
+    pub async fn connect(
+        self,
+        server: &Url,
+        cookie: http::HeaderValue,
+    ) -> eyre::Result<Connection> {
+        let url = hanab_live::websocket_url(server)?;
+        match self {
+            Self::Ezsockets => Ezsockets::connect(url, cookie).await,
+            Self::Tungstenite => Tungstenite::connect(url, cookie).await,
+        }
+    }
+}
+
+// Whether the server turned the connection down, as it does when the session
+// cookie has expired. Other failures, like the network being down, aren't
+// the cookie's fault.
+pub fn is_rejected(error: &eyre::Report) -> bool {
+    error.chain().any(|e| {
+        matches!(
+            e.downcast_ref::<WsError>(),
+            Some(WsError::Http(response))
+                if matches!(
+                    response.status(),
+                    http::StatusCode::UNAUTHORIZED | http::StatusCode::FORBIDDEN
+                )
+        )
+    })
+}
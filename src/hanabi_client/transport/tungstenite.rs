@@ -0,0 +1,67 @@
+use color_eyre::eyre;
+use futures::prelude::*;
+use hanab_live::Url;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::{Connection, Incoming, Transport};
+
+#[derive(Debug)]
+pub struct Tungstenite {
+    outgoing: mpsc::UnboundedSender<Message>,
+}
+
+impl Tungstenite {
+    pub async fn connect(
+        url: Url,
+        cookie: http::HeaderValue,
+    ) -> eyre::Result<Connection> {
+        let mut request = url.as_str().into_client_request()?;
+        request.headers_mut().insert(http::header::COOKIE, cookie);
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        let (mut sink, mut stream) = stream.split();
+
+        let (outgoing, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                if let Err(e) = sink.send(message).await {
+                    tracing::error!("websocket send failed: {e}");
+                    break;
+                }
+            }
+        });
+        let (tx, incoming) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(message) = stream.next().await {
+                let incoming = match message {
+                    Ok(Message::Text(text)) => Incoming::Text(text),
+                    Ok(Message::Binary(bytes)) => Incoming::Binary(bytes),
+                    Ok(Message::Close(_)) => break,
+                    // tungstenite answers pings itself
+                    Ok(_) => continue,
+                    Err(e) => Incoming::Error(e.into()),
+                };
+                if tx.send(incoming).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Connection {
+            transport: Box::new(Self { outgoing }),
+            incoming,
+        })
+    }
+}
+
+impl Transport for Tungstenite {
+    fn send(&self, text: String) {
+        // The send task only stops once the connection is gone, and then
+        // the incoming stream reports it
+        let _ = self.outgoing.send(Message::Text(text));
+    }
+
+    fn close(&self) {
+        let _ = self.outgoing.send(Message::Close(None));
+    }
+}
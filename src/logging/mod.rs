@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::{self, WrapErr};
+use tracing::field::{Field, Visit};
+use tracing::span;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::filter::{DynFilterFn, EnvFilter};
+use tracing_subscriber::fmt::format::{DefaultFields, Writer};
+use tracing_subscriber::fmt::{FormatFields, MakeWriter};
+use tracing_subscriber::layer::{Context, Layer, Layered};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+// Logging options, shared by every mode
+#[derive(Debug, clap::Args)]
+pub struct LogArgs {
+    // Which logs to show, in RUST_LOG syntax, e.g.
+    // "info,ejwu_rust_hanab_live_bot::strategy=debug". Defaults to RUST_LOG,
+    // then to info.
+    #[arg(long, value_name = "FILTER", global = true)]
+    log: Option<String>,
+    #[arg(long, value_enum, default_value_t, global = true)]
+    log_format: LogFormat,
+    // Also write each bot's logs to DIR/<username>.log.<date>, starting a new
+    // file every day
+    #[arg(long, value_name = "DIR", global = true)]
+    log_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // One JSON object per line, with span fields
+    Json,
+}
+
+// The filter goes first so that it applies to every output
+type Filtered = Layered<EnvFilter, Registry>;
+type BoxedLayer = Box<dyn Layer<Filtered> + Send + Sync>;
+
+// Install the global subscriber. Logs go to stderr and, with a log dir, to
+// a file per bot in usernames. A bot's logs are the ones inside a span with
+// its username field.
+pub fn init(args: &LogArgs, usernames: &[String]) -> eyre::Result<()> {
+    subscriber(args, usernames, std::io::stderr, true)?.try_init()?;
+    Ok(())
+}
+
+fn subscriber<W>(
+    args: &LogArgs,
+    usernames: &[String],
+    stderr: W,
+    ansi: bool,
+) -> eyre::Result<Layered<Vec<BoxedLayer>, Filtered>>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = match &args.log {
+        Some(filter) => EnvFilter::try_new(filter)
+            .wrap_err_with(|| format!("invalid --log filter {filter:?}"))?,
+        None => EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let mut layers: Vec<BoxedLayer> =
+        vec![UsernameLayer.boxed(), format(args.log_format, stderr, ansi)];
+    if let Some(dir) = &args.log_dir {
+        std::fs::create_dir_all(dir)?;
+        for username in usernames {
+            let file = tracing_appender::rolling::daily(
+                dir,
+                format!("{username}.log"),
+            );
+            let username = username.clone();
+            let only_this_bot = DynFilterFn::new(
+                move |metadata, cx: &Context<'_, Filtered>| {
+                    metadata.is_span() || in_span_for(cx, &username)
+                },
+            );
+            layers.push(
+                format(args.log_format, file, false)
+                    .with_filter(only_this_bot)
+                    .boxed(),
+            );
+        }
+    }
+    Ok(tracing_subscriber::registry().with(filter).with(layers))
+}
+
+fn format<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text if ansi => layer.boxed(),
+        LogFormat::Text => layer.fmt_fields(PlainFields::default()).boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+// Span fields are formatted once per formatter type and shared between
+// layers, so layers without colors need a type of their own
+#[derive(Default)]
+struct PlainFields(DefaultFields);
+
+impl<'w> FormatFields<'w> for PlainFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        writer: Writer<'w>,
+        fields: R,
+    ) -> std::fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+}
+
+// Span for one of the bots being run, numbered in the order they were given
+pub fn bot_span(i: usize, username: &str) -> tracing::Span {
+    tracing::info_span!("bot", i, username)
+}
+
+// The username field of a span, kept in its extensions for in_span_for
+struct Username(String);
+
+struct UsernameLayer;
+
+impl<S> Layer<S> for UsernameLayer
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        cx: Context<'_, S>,
+    ) {
+        let mut visitor = UsernameVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(username), Some(span)) = (visitor.0, cx.span(id)) {
+            span.extensions_mut().insert(Username(username));
+        }
+    }
+}
+
+struct UsernameVisitor(Option<String>);
+
+impl Visit for UsernameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "username" {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "username" {
+            self.0 = Some(format!("{value:?}").trim_matches('"').to_owned());
+        }
+    }
+}
+
+// Whether we're inside a span for this username
+fn in_span_for(cx: &Context<'_, Filtered>, username: &str) -> bool {
+    cx.lookup_current().is_some_and(|span| {
+        span.scope().any(|span| {
+            span.extensions()
+                .get::<Username>()
+                .is_some_and(|x| x.0 == username)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // Collects everything written to it
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MakeWriter<'_> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    fn logged(filter: &str, log: impl FnOnce()) -> String {
+        let args = LogArgs {
+            log: Some(filter.to_owned()),
+            log_format: LogFormat::Text,
+            log_dir: None,
+        };
+        let captured = Captured::default();
+        let subscriber = subscriber(&args, &[], captured.clone(), false)
+            .expect("valid filter");
+        tracing::subscriber::with_default(subscriber, log);
+        let text = captured.0.lock().unwrap().clone();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn filter_drops_lower_levels() {
+        let text = logged("info", || {
+            tracing::debug!("hidden");
+            tracing::info!("shown");
+        });
+        assert!(text.contains("shown"), "{text}");
+        assert!(!text.contains("hidden"), "{text}");
+    }
+
+    #[test]
+    fn filter_applies_per_target() {
+        let text = logged("info,hyper=error", || {
+            tracing::info!(target: "hyper::proto", "hidden");
+            tracing::info!("shown");
+        });
+        assert!(text.contains("shown"), "{text}");
+        assert!(!text.contains("hidden"), "{text}");
+    }
+}
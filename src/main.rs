@@ -2,24 +2,45 @@
 #![allow(clippy::wildcard_imports)]
 
 mod chat_command;
-mod command;
+mod config;
+mod console;
+#[cfg(unix)]
+mod control;
+mod daemon;
+mod eval;
+mod fleet;
+mod game;
+mod grade;
 mod hanabi_client;
+mod logging;
+mod metrics;
+mod provision;
+mod strategy;
 
-use std::collections::HashMap;
+use std::path::PathBuf;
 
 use clap::Parser;
-use color_eyre::eyre::{self, eyre};
-use command::client;
+use color_eyre::eyre::{self, WrapErr};
 use futures::prelude::*;
 use futures::stream::FuturesUnordered;
-use serde::Deserialize;
+use hanab_live::command::client;
+use tracing::Instrument;
 
-use crate::hanabi_client::Bot;
+use crate::config::{Config, Selection};
+use crate::fleet::Fleet;
+use crate::hanabi_client::{
+    Bot, ConnectOptions, Recording, SessionCache, TransportKind,
+};
 
 // Args apply to all bots, except create: one bot creates a table and the
 // others all join it.
 #[derive(clap::Parser)]
+#[allow(clippy::struct_excessive_bools)]
 struct Args {
+    #[command(subcommand)]
+    mode: Option<Mode>,
+    #[command(flatten)]
+    log: logging::LogArgs,
     // Number of bots to run. Will use default usernames.
     #[arg(
         short,
@@ -54,63 +75,236 @@ struct Args {
     // all future tables until changed.
     #[arg(short, long)]
     password: Option<String>,
+    // Post each bot's reasoning to table chat after the game
+    #[arg(long)]
+    explain: bool,
+    // Websocket library used to talk to hanab.live
+    #[arg(long, value_enum, default_value_t)]
+    transport: TransportKind,
+    // Record all bots' websocket traffic to this JSONL file, for replay
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+    // Bot accounts and settings. TOML if the name ends in .toml, JSON
+    // otherwise.
+    #[arg(long, value_name = "FILE", default_value = "config.json")]
+    config: PathBuf,
+    // Where session cookies are kept between runs, so bots don't have to log
+    // in every time. Defaults to the user cache directory.
+    #[arg(long, value_name = "DIR", group = "sessions")]
+    session_dir: Option<PathBuf>,
+    // Always log in, and don't keep session cookies
+    #[arg(long, group = "sessions")]
+    no_session_cache: bool,
+    // Keep the bots in the lobby indefinitely. Anyone can PM a bot /join to
+    // have it join their table, and it goes back to the lobby after the
+    // game. Bots reconnect if they're disconnected.
+    #[arg(long, conflicts_with_all = ["create", "table", "follow_user"])]
+    daemon: bool,
+    // Read orders for the bots from the terminal, and print what they see.
+    // Quitting shuts them all down.
+    #[arg(long, conflicts_with = "daemon")]
+    console: bool,
+    // Serve Prometheus metrics for every bot at http://ADDR/metrics
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<std::net::SocketAddr>,
+    // PMed, on shutdown, by each bot at a table to the user it's following
+    // or who invited it there
+    #[arg(long, value_name = "MESSAGE")]
+    farewell: Option<String>,
+    // Listen on this Unix socket for JSON commands to list the bots and
+    // steer them while they run
+    #[cfg(unix)]
+    #[arg(long, value_name = "SOCKET")]
+    control: Option<PathBuf>,
 }
 
-#[derive(Deserialize)]
-struct Config {
-    // Bot username, password
-    bots: HashMap<String, String>,
-    // List of default bot usernames
-    default_bots: Vec<String>,
+impl Args {
+    fn selection(&self) -> Selection<'_> {
+        self.user
+            .as_ref()
+            .map_or(Selection::Default(self.n), |users| Selection::Users(users))
+    }
+    // Everything that runs alongside the bots
+    fn spawn_services(&self, fleet: &Fleet) -> eyre::Result<()> {
+        if let Some(addr) = self.metrics {
+            metrics::spawn(addr)?;
+        }
+        tokio::spawn(shut_down_on_signal(Signals::new()?, fleet.clone()));
+        #[cfg(unix)]
+        if let Some(path) = &self.control {
+            control::spawn(path.clone(), fleet.clone());
+        }
+        Ok(())
+    }
+    fn connect_options(&self) -> eyre::Result<ConnectOptions> {
+        Ok(ConnectOptions {
+            transport: self.transport,
+            recording: self
+                .record
+                .as_deref()
+                .map(Recording::create)
+                .transpose()?,
+            sessions: self.session_cache(),
+        })
+    }
+    fn session_cache(&self) -> Option<SessionCache> {
+        if self.no_session_cache {
+            return None;
+        }
+        self.session_dir
+            .clone()
+            .or_else(SessionCache::default_dir)
+            .map(SessionCache::new)
+    }
+}
+
+// Modes other than running bots
+#[derive(clap::Subcommand)]
+enum Mode {
+    // Simulate games offline and report strategy performance
+    Eval(eval::EvalArgs),
+    // Compare a strategy's choices against a hanab.live game export
+    Grade(grade::GradeArgs),
+    // Check that a recording made with --record replays identically
+    Replay(hanabi_client::replay::ReplayArgs),
+    // Register new bot accounts and add them to the config
+    Provision(provision::ProvisionArgs),
+}
+
+impl Mode {
+    async fn run(&self) -> eyre::Result<()> {
+        match self {
+            Self::Eval(eval_args) => eval::run(eval_args),
+            Self::Grade(grade_args) => grade::run(grade_args),
+            Self::Replay(replay_args) => {
+                hanabi_client::replay::run(replay_args)
+            }
+            Self::Provision(provision_args) => {
+                provision::run(provision_args).await
+            }
+        }
+    }
+}
+
+// The first Ctrl-C or SIGTERM has the bots leave their tables and disconnect,
+// after which main returns. The second exits right away.
+async fn shut_down_on_signal(mut signals: Signals, fleet: Fleet) {
+    signals.recv().await;
+    tracing::info!("shutting down, signal again to exit immediately");
+    fleet.shutdown_all();
+    signals.recv().await;
+    std::process::exit(130);
+}
+
+// Ctrl-C, and SIGTERM on unix. The handlers are installed up front, so not
+// being able to install them is an error at startup.
+struct Signals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(not(unix))]
+    ctrl_c: tokio::signal::windows::CtrlC,
+}
+
+impl Signals {
+    fn new() -> eyre::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                interrupt: signal(SignalKind::interrupt())
+                    .wrap_err("installing SIGINT handler")?,
+                terminate: signal(SignalKind::terminate())
+                    .wrap_err("installing SIGTERM handler")?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {
+            ctrl_c: tokio::signal::windows::ctrl_c()
+                .wrap_err("installing Ctrl-C handler")?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = self.interrupt.recv() => {}
+            _ = self.terminate.recv() => {}
+        }
+        #[cfg(not(unix))]
+        self.ctrl_c.recv().await;
+    }
 }
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     color_eyre::install()?;
-    tracing_subscriber::fmt().init();
 
-    // Synchronous
-    let f = std::fs::read_to_string("config.json")?;
-    let config: Config = serde_json::from_str(&f)?;
     let args = Args::parse();
+    if let Some(mode) = &args.mode {
+        logging::init(&args.log, &[])?;
+        return mode.run().await;
+    }
 
-    let bot_usernames = match &args.user {
-        Some(users) => users.as_slice(),
-        None => &config.default_bots[0..args.n],
-    };
-    let bot_username_passwords = bot_usernames.iter().map(|username| {
-        let username = username.clone();
-        let password = config.bots[&username].clone();
-        (username, password)
-    });
-    let mut bot_new_results: FuturesUnordered<_> = bot_username_passwords
+    // Synchronous
+    let bot_configs = Config::load(&args.config)?.select(args.selection())?;
+    let bot_usernames: Vec<_> =
+        bot_configs.iter().map(|x| x.username.clone()).collect();
+    logging::init(&args.log, &bot_usernames)?;
+    let options = args.connect_options()?;
+    let fleet = Fleet::new(args.farewell.clone());
+    args.spawn_services(&fleet)?;
+    if args.daemon {
+        daemon::run(bot_configs, options, args.explain, fleet).await;
+        return Ok(());
+    }
+
+    let mut bot_new_results: FuturesUnordered<_> = bot_configs
+        .iter()
+        .cloned()
         .enumerate()
-        .map(|(i, (username, password))| {
-            tokio::spawn(
-                async move { (i, Bot::new(&username, &password).await) },
-            )
+        .map(|(i, config)| {
+            let options = options.clone();
+            tokio::spawn(async move { (i, Bot::new(&config, &options).await) })
         })
         .collect();
 
-    // Collection that holds running bot futures; the bots are spawned by
-    // ezsockets when created (kinda bad API) so no need to spawn them
+    // Collection that holds running bot futures; the bots are spawned when
+    // created so no need to spawn them
     let mut running_bot_futures = FuturesUnordered::new();
     // Helper function to process args
     // Should impl Fn
     let process_args_for_bot = |i: usize, bot: Bot| {
-        if args.create {
+        bot.explain(args.explain || bot_configs[i].explain);
+        let request = if args.create {
             match i {
-                0 => bot.create_table(client::TableCreate {
-                    name: args.table.clone(),
-                    ..client::TableCreate::default()
-                }),
-                _ => bot.follow_user(bot_usernames[0].clone()),
+                0 => bot
+                    .create_table(client::TableCreate {
+                        name: args.table.clone(),
+                        password: args.password.clone(),
+                        ..client::TableCreate::default()
+                    })
+                    .boxed(),
+                _ => bot.follow_user(bot_usernames[0].clone()).boxed(),
             }
         } else if let Some(user) = &args.follow_user {
-            bot.follow_user(user.clone());
+            bot.follow_user(user.clone()).boxed()
         } else if let Some(table) = &args.table {
-            bot.join_table(table.clone());
-        }
+            bot.join_table(table.clone()).boxed()
+        } else {
+            return;
+        };
+        let span = logging::bot_span(i, &bot.username);
+        tokio::spawn(
+            async move {
+                match request.await {
+                    Ok(table_id) => tracing::info!("joined table {table_id}"),
+                    Err(e) => tracing::error!("couldn't get to a table: {e}"),
+                }
+            }
+            .instrument(span),
+        );
     };
 
     while let Some(join_result) = bot_new_results.next().await {
@@ -118,32 +312,49 @@ async fn main() -> eyre::Result<()> {
         let (i, result) = join_result.unwrap();
         match result {
             Ok((bot, future)) => {
+                fleet.insert(bot.clone());
                 process_args_for_bot(i, bot);
                 running_bot_futures.push(async move { (i, future.await) });
             }
             Err(e) => {
-                tracing::error!(
-                    "bot[{i}] {{username={:?}}} error when starting: {e}",
-                    bot_usernames[i]
-                );
+                let _span = logging::bot_span(i, &bot_usernames[i]).entered();
+                tracing::error!("error when starting: {e}");
             }
         }
     }
+    if args.console {
+        tokio::spawn(console::run(fleet.clone()));
+    }
     while let Some((i, result)) = running_bot_futures.next().await {
+        let _span = logging::bot_span(i, &bot_usernames[i]).entered();
         match result {
-            Ok(()) => {
-                tracing::info!(
-                    "bot[{i}] {{username={:?}}} finished",
-                    bot_usernames[i]
-                );
-            }
-            Err(e) => {
-                tracing::error!(
-                    "bot[{i}] {{username={:?}}} terminated with error: {e}",
-                    bot_usernames[i]
-                );
-            }
+            Ok(()) => tracing::info!("finished"),
+            Err(e) => tracing::error!("terminated with error: {e}"),
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_cache_flags() {
+        let args = Args::try_parse_from(["bot", "--no-session-cache"]).unwrap();
+        assert!(args.session_cache().is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+        let args = Args::try_parse_from(["bot", "--session-dir", dir]).unwrap();
+        let server = "https://hanab.live/".parse().unwrap();
+        let cookie = http::HeaderValue::from_static("hanabi.sid=abc");
+        let cache = args.session_cache().unwrap();
+        cache.store(&server, "bot", &cookie).unwrap();
+        let cache = SessionCache::new(dir.into());
+        assert_eq!(cache.load(&server, "bot"), Some(cookie));
+
+        let both = ["bot", "--no-session-cache", "--session-dir", dir];
+        assert!(Args::try_parse_from(both).is_err());
+    }
+}
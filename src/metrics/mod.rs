@@ -0,0 +1,242 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use color_eyre::eyre::{self, WrapErr};
+use hanab_live::command::TableID;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+// Every bot's metrics, labelled with its username
+struct Metrics {
+    registry: Registry,
+    connected: IntGaugeVec,
+    table: IntGaugeVec,
+    games: IntCounterVec,
+    scores: HistogramVec,
+    strikes: IntCounterVec,
+    reconnects: IntCounterVec,
+    sent: IntCounterVec,
+    received: IntCounterVec,
+    server_errors: IntCounterVec,
+    decision_seconds: HistogramVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("hanab".to_owned()), None).unwrap();
+        let bot = &["bot"];
+        let command = &["bot", "command"];
+        let metrics = Self {
+            connected: IntGaugeVec::new(
+                Opts::new("connected", "Whether the bot is connected"),
+                bot,
+            )
+            .unwrap(),
+            table: IntGaugeVec::new(
+                Opts::new("table", "Table the bot is at, or 0"),
+                bot,
+            )
+            .unwrap(),
+            games: IntCounterVec::new(
+                Opts::new("games_played_total", "Games played to the end"),
+                bot,
+            )
+            .unwrap(),
+            scores: HistogramVec::new(
+                HistogramOpts::new("game_score", "Final score of each game")
+                    .buckets(vec![
+                        0.0, 5.0, 10.0, 15.0, 20.0, 21.0, 22.0, 23.0, 24.0,
+                        25.0, 30.0,
+                    ]),
+                bot,
+            )
+            .unwrap(),
+            strikes: IntCounterVec::new(
+                Opts::new("strikes_total", "Strikes in games played"),
+                bot,
+            )
+            .unwrap(),
+            reconnects: IntCounterVec::new(
+                Opts::new("reconnects_total", "Reconnects after a disconnect"),
+                bot,
+            )
+            .unwrap(),
+            sent: IntCounterVec::new(
+                Opts::new("commands_sent_total", "Commands sent to the server"),
+                command,
+            )
+            .unwrap(),
+            received: IntCounterVec::new(
+                Opts::new(
+                    "commands_received_total",
+                    "Commands received from the server",
+                ),
+                command,
+            )
+            .unwrap(),
+            server_errors: IntCounterVec::new(
+                Opts::new("server_errors_total", "Errors sent by the server"),
+                bot,
+            )
+            .unwrap(),
+            decision_seconds: HistogramVec::new(
+                HistogramOpts::new(
+                    "decision_seconds",
+                    "Time the strategy took to decide a move",
+                )
+                .buckets(
+                    prometheus::exponential_buckets(0.001, 4.0, 8).unwrap(),
+                ),
+                bot,
+            )
+            .unwrap(),
+            registry,
+        };
+        for collector in [
+            Box::new(metrics.connected.clone())
+                as Box<dyn prometheus::core::Collector>,
+            Box::new(metrics.table.clone()),
+            Box::new(metrics.games.clone()),
+            Box::new(metrics.scores.clone()),
+            Box::new(metrics.strikes.clone()),
+            Box::new(metrics.reconnects.clone()),
+            Box::new(metrics.sent.clone()),
+            Box::new(metrics.received.clone()),
+            Box::new(metrics.server_errors.clone()),
+            Box::new(metrics.decision_seconds.clone()),
+        ] {
+            metrics.registry.register(collector).unwrap();
+        }
+        metrics
+    }
+}
+
+// One bot's metrics. Cheap to make, so anything can record for a bot by
+// username.
+#[derive(Clone)]
+pub struct BotMetrics {
+    username: String,
+    connected: IntGauge,
+    table: IntGauge,
+    games: IntCounter,
+    scores: Histogram,
+    strikes: IntCounter,
+    reconnects: IntCounter,
+    server_errors: IntCounter,
+    decision_seconds: Histogram,
+}
+
+impl std::fmt::Debug for BotMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BotMetrics")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BotMetrics {
+    pub fn new(username: &str) -> Self {
+        let metrics = &*METRICS;
+        let bot = &[username];
+        Self {
+            username: username.to_owned(),
+            connected: metrics.connected.with_label_values(bot),
+            table: metrics.table.with_label_values(bot),
+            games: metrics.games.with_label_values(bot),
+            scores: metrics.scores.with_label_values(bot),
+            strikes: metrics.strikes.with_label_values(bot),
+            reconnects: metrics.reconnects.with_label_values(bot),
+            server_errors: metrics.server_errors.with_label_values(bot),
+            decision_seconds: metrics.decision_seconds.with_label_values(bot),
+        }
+    }
+
+    pub fn connected(&self, connected: bool) {
+        self.connected.set(connected.into());
+    }
+
+    pub fn table(&self, table_id: Option<TableID>) {
+        let table_id = table_id.map_or(0, u64::from);
+        self.table.set(i64::try_from(table_id).unwrap_or(i64::MAX));
+    }
+
+    pub fn reconnected(&self) {
+        self.reconnects.inc();
+    }
+
+    pub fn command_sent(&self, command: &str) {
+        METRICS
+            .sent
+            .with_label_values(&[&self.username, command])
+            .inc();
+    }
+
+    // The name is the first word of the message
+    pub fn command_received(&self, text: &str) {
+        let command = text.split_once(' ').map_or(text, |(name, _)| name);
+        METRICS
+            .received
+            .with_label_values(&[&self.username, command])
+            .inc();
+    }
+
+    pub fn server_error(&self) {
+        self.server_errors.inc();
+    }
+
+    pub fn game_over(&self, score: u32, strikes: u8) {
+        self.games.inc();
+        self.scores.observe(f64::from(score));
+        self.strikes.inc_by(strikes.into());
+    }
+
+    pub fn decided(&self, elapsed: Duration) {
+        self.decision_seconds.observe(elapsed.as_secs_f64());
+    }
+}
+
+// Serve GET /metrics in the Prometheus text format, in the background.
+// Fails if the address can't be bound.
+pub fn spawn(addr: SocketAddr) -> eyre::Result<()> {
+    let server = hyper::Server::try_bind(&addr)
+        .wrap_err_with(|| format!("binding {addr}"))?
+        .serve(make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(respond))
+        }));
+    tracing::info!("serving metrics on http://{addr}/metrics");
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            tracing::error!("metrics server stopped: {e}");
+        }
+    });
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+async fn respond(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        let mut response = Response::new(Body::from("not found\n"));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+        .encode(&METRICS.registry.gather(), &mut body)
+        .unwrap();
+    let mut response = Response::new(Body::from(body));
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        encoder.format_type().parse().unwrap(),
+    );
+    Ok(response)
+}
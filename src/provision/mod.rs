@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, bail, eyre, WrapErr};
+use hanab_live::Url;
+use rand::distributions::{Alphanumeric, DistString};
+
+use crate::hanabi_client::write_private;
+
+// Register new bot accounts and add them to the config. hanab.live creates
+// an account the first time someone logs in with it.
+#[derive(Debug, clap::Args)]
+pub struct ProvisionArgs {
+    // Number of accounts to create
+    #[arg(short, default_value_t = 1)]
+    n: usize,
+    // Usernames are the prefix followed by a number. Numbers already in the
+    // config are skipped.
+    #[arg(long)]
+    prefix: String,
+    // Config file to add the bots to, as bots and default_bots. Created if
+    // it doesn't exist.
+    #[arg(long, value_name = "FILE", default_value = "config.json")]
+    config: PathBuf,
+    // Base URL of the hanab.live server to register on
+    #[arg(long, default_value = hanab_live::DEFAULT_SERVER)]
+    server: Url,
+}
+
+const PASSWORD_LENGTH: usize = 24;
+// A failed login usually means someone else has the username, but many in a
+// row means something is wrong
+const MAX_FAILURES: usize = 5;
+
+pub async fn run(args: &ProvisionArgs) -> eyre::Result<()> {
+    let mut config = ConfigDoc::load(&args.config)?;
+    let taken = config.usernames()?;
+    let mut usernames = (0..)
+        .map(|i| format!("{}{i}", args.prefix))
+        .filter(|x| !taken.contains(x));
+    let mut created = 0;
+    let mut failures = 0;
+    while created < args.n {
+        let username = usernames.next().unwrap();
+        let password = Alphanumeric
+            .sample_string(&mut rand::thread_rng(), PASSWORD_LENGTH);
+        let login = hanab_live::authenticate_and_get_cookie(
+            &args.server,
+            &username,
+            &password,
+        )
+        .await;
+        if let Err(e) = login {
+            tracing::warn!("couldn't register {username}: {e}");
+            failures += 1;
+            if failures == MAX_FAILURES {
+                bail!("gave up after {failures} failed logins");
+            }
+            continue;
+        }
+        // Saved right away, so the password isn't lost if a later one fails
+        config.add_bot(&username, &password)?;
+        config.save(&args.config)?;
+        created += 1;
+        println!("registered {username}");
+    }
+    println!("added {created} bots to {}", args.config.display());
+    Ok(())
+}
+
+// The config file, edited in place so the rest of it is left alone. TOML if
+// the name ends in .toml, JSON otherwise, like when it's loaded.
+enum ConfigDoc {
+    Json(serde_json::Value),
+    Toml(toml_edit::DocumentMut),
+}
+
+impl ConfigDoc {
+    fn load(path: &Path) -> eyre::Result<Self> {
+        let toml = path.extension().is_some_and(|x| x == "toml");
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e)
+                    .wrap_err_with(|| format!("reading {}", path.display()))
+            }
+        };
+        let doc = if toml {
+            Self::Toml(text.parse()?)
+        } else if text.is_empty() {
+            Self::Json(serde_json::json!({}))
+        } else {
+            Self::Json(serde_json::from_str(&text)?)
+        };
+        Ok(doc)
+    }
+
+    fn usernames(&self) -> eyre::Result<HashSet<String>> {
+        let usernames = match self {
+            Self::Json(value) => match value.get("bots") {
+                Some(bots) => bots
+                    .as_object()
+                    .ok_or_else(|| eyre!("bots isn't an object"))?
+                    .keys()
+                    .cloned()
+                    .collect(),
+                None => HashSet::new(),
+            },
+            Self::Toml(doc) => match doc.get("bots") {
+                Some(bots) => bots
+                    .as_table_like()
+                    .ok_or_else(|| eyre!("bots isn't a table"))?
+                    .iter()
+                    .map(|(username, _)| username.to_owned())
+                    .collect(),
+                None => HashSet::new(),
+            },
+        };
+        Ok(usernames)
+    }
+
+    fn add_bot(&mut self, username: &str, password: &str) -> eyre::Result<()> {
+        match self {
+            Self::Json(value) => {
+                let root = value
+                    .as_object_mut()
+                    .ok_or_else(|| eyre!("config isn't an object"))?;
+                root.entry("bots")
+                    .or_insert_with(|| serde_json::json!({}))
+                    .as_object_mut()
+                    .ok_or_else(|| eyre!("bots isn't an object"))?
+                    .insert(username.to_owned(), password.into());
+                root.entry("default_bots")
+                    .or_insert_with(|| serde_json::json!([]))
+                    .as_array_mut()
+                    .ok_or_else(|| eyre!("default_bots isn't an array"))?
+                    .push(username.into());
+            }
+            Self::Toml(doc) => {
+                doc.entry("bots")
+                    .or_insert_with(toml_edit::table)
+                    .as_table_like_mut()
+                    .ok_or_else(|| eyre!("bots isn't a table"))?
+                    .insert(username, toml_edit::value(password));
+                doc.entry("default_bots")
+                    .or_insert_with(
+                        || toml_edit::value(toml_edit::Array::new()),
+                    )
+                    .as_array_mut()
+                    .ok_or_else(|| eyre!("default_bots isn't an array"))?
+                    .push(username);
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, path: &Path) -> eyre::Result<()> {
+        let text = match self {
+            Self::Json(value) => serde_json::to_string_pretty(value)? + "\n",
+            Self::Toml(doc) => doc.to_string(),
+        };
+        // It holds the new passwords
+        write_private(path, text.as_bytes())
+            .wrap_err_with(|| format!("writing {}", path.display()))
+    }
+}
@@ -0,0 +1,203 @@
+use std::time::Instant;
+
+use super::{Decision, Rationale, Strategy};
+use crate::game::{
+    Action, Card, CardOrder, Clue, PlayerView, Possibilities, PublicState,
+    MAX_CLUE_TOKENS,
+};
+
+// A convention-free strategy: cards are only played once clues prove they
+// are playable, so it never bombs but needs about two clues per play.
+#[derive(Debug)]
+pub struct Basic;
+
+impl Strategy for Basic {
+    fn decide(
+        &mut self,
+        view: PlayerView,
+        _deadline: Option<Instant>,
+    ) -> Decision {
+        known_playable(view)
+            .map(|order| {
+                Decision::new(Action::Play(order), Rationale::KnownPlayable)
+            })
+            .or_else(|| play_clue(view))
+            .or_else(|| save_clue(view))
+            .or_else(|| discard(view))
+            .unwrap_or_else(|| fallback(view))
+    }
+}
+
+fn all_playable(public: PublicState, possible: Possibilities) -> bool {
+    !possible.is_empty() && possible.iter().all(|card| public.is_playable(card))
+}
+
+fn all_trash(public: PublicState, possible: Possibilities) -> bool {
+    !possible.is_empty() && possible.iter().all(|card| public.is_trash(card))
+}
+
+fn known_playable(view: PlayerView) -> Option<CardOrder> {
+    let public = view.public();
+    // Oldest first
+    public
+        .hand(view.seat())
+        .iter()
+        .rev()
+        .copied()
+        .find(|&order| all_playable(public, public.knowledge(order).possible))
+}
+
+// Whether someone already has this card clued, so cluing another copy would
+// be wasted
+fn already_clued(view: PlayerView, card: Card, except: CardOrder) -> bool {
+    let public = view.public();
+    (0..public.num_players()).any(|player| {
+        public.hand(player).iter().any(|&order| {
+            let knowledge = public.knowledge(order);
+            order != except
+                && knowledge.clued
+                && (view.card(order) == Some(card)
+                    || knowledge.possible.known() == Some(card))
+        })
+    })
+}
+
+fn color_clue(view: PlayerView, card: Card) -> Clue {
+    let variant = view.public().variant();
+    // Rainbow cards are touched by any color
+    Clue::Color(
+        variant
+            .clue_colors()
+            .position(|suit| suit == card.suit)
+            .unwrap_or(0),
+    )
+}
+
+// Other players, starting with the next one
+fn teammates(view: PlayerView) -> impl Iterator<Item = usize> {
+    let num_players = view.public().num_players();
+    let seat = view.seat();
+    (1..num_players).map(move |i| (seat + i) % num_players)
+}
+
+fn touches_trash(view: PlayerView, target: usize, clue: Clue) -> bool {
+    view.touched(target, clue).iter().any(|&order| {
+        view.card(order)
+            .is_some_and(|card| view.public().is_trash(card))
+    })
+}
+
+fn play_clue(view: PlayerView) -> Option<Decision> {
+    let public = view.public();
+    if public.clue_tokens() == 0 {
+        return None;
+    }
+    let variant = public.variant();
+    let candidates: Vec<(usize, CardOrder, Card)> = teammates(view)
+        .flat_map(|target| {
+            public.hand(target).iter().filter_map(move |&order| {
+                let card = view.card(order)?;
+                let known =
+                    all_playable(public, public.knowledge(order).possible);
+                (public.is_playable(card)
+                    && !known
+                    && !already_clued(view, card, order))
+                .then_some((target, order, card))
+            })
+        })
+        .collect();
+    // Prefer a clue that lets the card be played right away
+    for &(target, order, card) in &candidates {
+        for clue in [Clue::Rank(card.rank), color_clue(view, card)] {
+            let possible = public
+                .knowledge(order)
+                .possible
+                .filter(|x| clue.touches(variant, x));
+            if all_playable(public, possible)
+                && !touches_trash(view, target, clue)
+            {
+                return Some(Decision::new(
+                    Action::Clue { target, clue },
+                    Rationale::PlayClue {
+                        order,
+                        card,
+                        complete: true,
+                    },
+                ));
+            }
+        }
+    }
+    // Otherwise start narrowing down a playable card
+    candidates
+        .iter()
+        .filter(|(_, order, _)| !public.knowledge(*order).clued)
+        .find_map(|&(target, order, card)| {
+            let clue = [Clue::Rank(card.rank), color_clue(view, card)]
+                .into_iter()
+                .find(|&clue| !touches_trash(view, target, clue))?;
+            Some(Decision::new(
+                Action::Clue { target, clue },
+                Rationale::PlayClue {
+                    order,
+                    card,
+                    complete: false,
+                },
+            ))
+        })
+}
+
+// Protect the next player's chop if it's the last copy of a needed card
+fn save_clue(view: PlayerView) -> Option<Decision> {
+    let public = view.public();
+    if public.clue_tokens() == 0 {
+        return None;
+    }
+    let target = teammates(view).next()?;
+    let chop = chop(public, target)?;
+    let card = view.card(chop)?;
+    public.is_critical(card).then_some(Decision::new(
+        Action::Clue {
+            target,
+            clue: Clue::Rank(card.rank),
+        },
+        Rationale::SaveClue { order: chop, card },
+    ))
+}
+
+// Oldest unclued card
+fn chop(public: PublicState, player: usize) -> Option<CardOrder> {
+    public
+        .hand(player)
+        .iter()
+        .rev()
+        .copied()
+        .find(|&order| !public.knowledge(order).clued)
+}
+
+fn discard(view: PlayerView) -> Option<Decision> {
+    let public = view.public();
+    if public.clue_tokens() == MAX_CLUE_TOKENS {
+        return None;
+    }
+    let hand = public.hand(view.seat());
+    let (order, rationale) = hand
+        .iter()
+        .rev()
+        .copied()
+        .find(|&order| all_trash(public, public.knowledge(order).possible))
+        .map(|order| (order, Rationale::KnownTrash))
+        .or_else(|| Some((chop(public, view.seat())?, Rationale::Chop)))
+        .or_else(|| Some((*hand.last()?, Rationale::OldestCard)))?;
+    Some(Decision::new(Action::Discard(order), rationale))
+}
+
+// Nothing useful to do at max clue tokens, so give any clue
+fn fallback(view: PlayerView) -> Decision {
+    let actions = view.legal_actions();
+    let action = actions
+        .iter()
+        .copied()
+        .find(|action| matches!(action, Action::Clue { .. }))
+        .unwrap_or(actions[0]);
+    Decision::new(action, Rationale::Stall)
+}
@@ -0,0 +1,117 @@
+use std::fmt::Debug;
+use std::time::Instant;
+
+use color_eyre::eyre::{self, WrapErr};
+
+use crate::game::{GameState, PlayerView, Variant};
+
+mod basic;
+mod random;
+mod rationale;
+
+pub use basic::Basic;
+pub use random::Random;
+pub use rationale::{describe_action, Decision, Rationale};
+
+pub trait Strategy: Debug + Send {
+    // Called on this player's turn. In timed games, the deadline is when the
+    // strategy must have returned; strategies that search should scale their
+    // effort to it.
+    fn decide(
+        &mut self,
+        view: PlayerView,
+        deadline: Option<Instant>,
+    ) -> Decision;
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyKind {
+    // Play known playables, clue playables and critical chops, discard chop
+    Basic,
+    // Uniformly random legal action. Useful as a baseline.
+    Random,
+}
+
+impl StrategyKind {
+    // The seed is only used by strategies with randomness
+    pub fn build(self, seed: u64) -> Box<dyn Strategy> {
+        match self {
+            Self::Basic => Box::new(Basic),
+            Self::Random => Box::new(Random::new(seed)),
+        }
+    }
+}
+
+// Play a whole game in the simulator, one strategy per seat
+pub fn play_game(
+    variant: &'static Variant,
+    seed: u64,
+    strategies: &mut [Box<dyn Strategy>],
+) -> eyre::Result<GameState> {
+    let mut state = GameState::from_seed(variant, strategies.len(), seed)?;
+    while state.end().is_none() {
+        let seat = state.current_player();
+        let view = PlayerView::new(&state, seat);
+        let action = strategies[seat].decide(view, None).action;
+        state.apply(action).wrap_err_with(|| {
+            format!("seat {seat} chose an illegal action {action:?}")
+        })?;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(kind: StrategyKind, players: usize, seed: u64) -> GameState {
+        let variant = Variant::from_name("No Variant").unwrap();
+        let mut strategies: Vec<_> =
+            (0..players).map(|seat| kind.build(seat as u64)).collect();
+        play_game(variant, seed, &mut strategies).unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        for kind in [StrategyKind::Basic, StrategyKind::Random] {
+            let first = play(kind, 3, 42);
+            let second = play(kind, 3, 42);
+            assert!(first.end().is_some());
+            assert_eq!(first.score(), second.score());
+            assert_eq!(first.turn(), second.turn());
+            assert_eq!(first.strikes(), second.strikes());
+        }
+    }
+
+    // Every game runs to the end without an illegal move
+    #[test]
+    fn games_end() {
+        for players in 2..=6 {
+            for seed in 0..10 {
+                for kind in [StrategyKind::Basic, StrategyKind::Random] {
+                    let game = play(kind, players, seed);
+                    assert!(game.end().is_some(), "{kind:?} {players} {seed}");
+                }
+            }
+        }
+    }
+
+    // Basic only plays cards that clues prove playable
+    #[test]
+    fn basic_never_strikes() {
+        for seed in 0..20 {
+            assert_eq!(play(StrategyKind::Basic, 3, seed).strikes(), 0);
+        }
+    }
+
+    #[test]
+    fn basic_beats_random() {
+        let total = |kind| -> u32 {
+            (0..20).map(|seed| play(kind, 2, seed).score()).sum()
+        };
+        assert!(total(StrategyKind::Basic) > total(StrategyKind::Random));
+    }
+}
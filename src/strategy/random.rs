@@ -0,0 +1,29 @@
+use std::time::Instant;
+
+use super::{Decision, Rationale, Strategy};
+use crate::game::{PlayerView, Rng};
+
+#[derive(Debug)]
+pub struct Random {
+    rng: Rng,
+}
+
+impl Random {
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Strategy for Random {
+    fn decide(
+        &mut self,
+        view: PlayerView,
+        _deadline: Option<Instant>,
+    ) -> Decision {
+        let actions = view.legal_actions();
+        let action = actions[self.rng.below(actions.len())];
+        Decision::new(action, Rationale::Random)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::game::{Action, Card, CardOrder, Clue, PlayerView};
+
+// What a strategy chose to do, and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub action: Action,
+    pub rationale: Rationale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rationale {
+    KnownPlayable,
+    // complete is whether the clue proves the card is playable
+    PlayClue {
+        order: CardOrder,
+        card: Card,
+        complete: bool,
+    },
+    SaveClue {
+        order: CardOrder,
+        card: Card,
+    },
+    KnownTrash,
+    Chop,
+    // Every card is clued, so the oldest goes
+    OldestCard,
+    // Nothing useful to do at max clue tokens
+    Stall,
+    Random,
+}
+
+impl Decision {
+    pub const fn new(action: Action, rationale: Rationale) -> Self {
+        Self { action, rationale }
+    }
+
+    // Human readable, e.g. for table chat. Takes the view from when the
+    // decision was made, so slot numbers are correct.
+    pub fn describe(&self, view: PlayerView) -> String {
+        let variant = view.public().variant();
+        let action = describe_action(view, self.action);
+        let reason = match self.rationale {
+            Rationale::KnownPlayable => "the clues prove it's playable".into(),
+            Rationale::PlayClue {
+                order,
+                card,
+                complete,
+            } => format!(
+                "{} {} is playable{}",
+                slot(view, order),
+                variant.card_name(card),
+                if complete {
+                    " and this clue proves it"
+                } else {
+                    ", this clue starts narrowing it down"
+                }
+            ),
+            Rationale::SaveClue { order, card } => format!(
+                "{} {} is a critical card on chop",
+                slot(view, order),
+                variant.card_name(card)
+            ),
+            Rationale::KnownTrash => "it's known trash".into(),
+            Rationale::Chop => "it's the oldest unclued card".into(),
+            Rationale::OldestCard => "every card is clued".into(),
+            Rationale::Stall => "nothing useful to do at max clues".into(),
+            Rationale::Random => "chosen at random".into(),
+        };
+        format!("{action}: {reason}")
+    }
+}
+
+pub fn describe_action(view: PlayerView, action: Action) -> String {
+    let variant = view.public().variant();
+    match action {
+        Action::Play(order) => format!("play {}", slot(view, order)),
+        Action::Discard(order) => format!("discard {}", slot(view, order)),
+        Action::Clue { target, clue } => {
+            let value = match clue {
+                Clue::Color(color) => variant
+                    .clue_colors()
+                    .nth(color)
+                    .map_or("?", |suit| variant.suits[suit].name)
+                    .to_owned(),
+                Clue::Rank(rank) => rank.to_string(),
+            };
+            format!("clue {value} to player {target}")
+        }
+    }
+}
+
+// "player 1 slot 2", counting slots from the newest card like hanab.live
+fn slot(view: PlayerView, order: CardOrder) -> String {
+    let public = view.public();
+    (0..public.num_players())
+        .find_map(|player| {
+            let slot = public.hand(player).iter().position(|&x| x == order)?;
+            Some(if player == view.seat() {
+                format!("slot {}", slot + 1)
+            } else {
+                format!("player {player} slot {}", slot + 1)
+            })
+        })
+        .unwrap_or_else(|| format!("card #{}", order.0))
+}